@@ -0,0 +1,194 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::state::AppState;
+use crate::common::glob::glob_match;
+use crate::core::config::{AuthConfig, VirtualKeyConfig};
+
+/// Name attached to requests when authentication is disabled.
+pub const ANONYMOUS_CLIENT: &str = "anonymous";
+
+/// The virtual key a request was authenticated with.
+#[derive(Clone, Debug)]
+pub struct ClientIdentity {
+    pub name: String,
+    models: Arc<Vec<String>>,
+    pub monthly_token_budget: Option<u64>,
+    pub monthly_cost_budget: Option<f64>,
+}
+
+impl ClientIdentity {
+    fn anonymous() -> Self {
+        Self {
+            name: ANONYMOUS_CLIENT.to_string(),
+            models: Arc::new(Vec::new()),
+            monthly_token_budget: None,
+            monthly_cost_budget: None,
+        }
+    }
+
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.models.is_empty() || self.models.iter().any(|m| glob_match(m, model))
+    }
+}
+
+struct KeyEntry {
+    identity: ClientIdentity,
+    enabled: bool,
+}
+
+impl From<&VirtualKeyConfig> for KeyEntry {
+    fn from(config: &VirtualKeyConfig) -> Self {
+        Self {
+            identity: ClientIdentity {
+                name: config.name.clone(),
+                models: Arc::new(config.models.clone()),
+                monthly_token_budget: config.monthly_token_budget,
+                monthly_cost_budget: config.monthly_cost_budget,
+            },
+            enabled: config.enabled,
+        }
+    }
+}
+
+/// Lookup table of configured virtual keys.
+pub struct ClientKeys {
+    keys: HashMap<String, KeyEntry>,
+    admin_key: Option<String>,
+}
+
+impl ClientKeys {
+    pub fn new(config: &AuthConfig) -> Self {
+        let keys = config
+            .keys
+            .iter()
+            .map(|k| (k.key.clone(), KeyEntry::from(k)))
+            .collect();
+        Self {
+            keys,
+            admin_key: config.admin_key.clone(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    fn identify(&self, key: &str) -> Option<&KeyEntry> {
+        self.keys.get(key)
+    }
+
+    /// Identity of an enabled key by name, for work done after the request that carried it,
+    /// such as batch execution. Anonymous while authentication is off.
+    pub fn identity_by_name(&self, name: &str) -> Option<ClientIdentity> {
+        if !self.is_enabled() {
+            return Some(ClientIdentity::anonymous());
+        }
+        self.keys
+            .values()
+            .find(|entry| entry.enabled && entry.identity.name == name)
+            .map(|entry| entry.identity.clone())
+    }
+
+    /// Admin endpoints need the admin key; without one they stay open only while
+    /// client authentication is off.
+    fn admits_admin(&self, key: Option<&str>) -> bool {
+        match &self.admin_key {
+            Some(admin_key) => key == Some(admin_key.as_str()),
+            None => !self.is_enabled(),
+        }
+    }
+}
+
+/// Reads the presented key from `Authorization: Bearer` (OpenAI) or `x-api-key` (Anthropic).
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        })
+        .map(str::trim)
+}
+
+/// Middleware that authenticates the caller and attaches a [`ClientIdentity`].
+pub async fn authenticate(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !state.client_keys.is_enabled() {
+        request.extensions_mut().insert(ClientIdentity::anonymous());
+        return next.run(request).await;
+    }
+
+    let format = ApiFormat::from_path(request.uri().path());
+    let key = match presented_key(request.headers()) {
+        Some(k) if !k.is_empty() => k,
+        _ => {
+            return error_response(
+                format,
+                StatusCode::UNAUTHORIZED,
+                "authentication_error",
+                "Missing API key",
+            );
+        }
+    };
+
+    let identity = match state.client_keys.identify(key) {
+        Some(entry) if entry.enabled => entry.identity.clone(),
+        Some(entry) => {
+            warn!(client = %entry.identity.name, "Rejected request with disabled API key");
+            return error_response(
+                format,
+                StatusCode::UNAUTHORIZED,
+                "authentication_error",
+                "API key is disabled",
+            );
+        }
+        None => {
+            warn!("Rejected request with unknown API key");
+            return error_response(
+                format,
+                StatusCode::UNAUTHORIZED,
+                "authentication_error",
+                "Invalid API key",
+            );
+        }
+    };
+
+    request.extensions_mut().insert(identity);
+    next.run(request).await
+}
+
+/// Middleware guarding the `/admin/*` endpoints.
+pub async fn authenticate_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state
+        .client_keys
+        .admits_admin(presented_key(request.headers()))
+    {
+        warn!(path = %request.uri().path(), "Rejected admin request");
+        return error_response(
+            ApiFormat::OpenAi,
+            StatusCode::UNAUTHORIZED,
+            "authentication_error",
+            "Invalid admin key",
+        );
+    }
+    next.run(request).await
+}
@@ -0,0 +1,143 @@
+use axum::{
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::core::limits::{BucketSnapshot, RateLimited};
+
+/// Which client-facing API surface an error is rendered for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiFormat {
+    OpenAi,
+    Anthropic,
+    /// Ollama's native API, whose errors are a bare `{"error": "..."}`.
+    Ollama,
+}
+
+impl ApiFormat {
+    /// Picks the error format from the request path; `/v1/messages*` is Anthropic and
+    /// `/api/*` is Ollama.
+    pub fn from_path(path: &str) -> Self {
+        if path.starts_with("/v1/messages") {
+            ApiFormat::Anthropic
+        } else if path.starts_with("/api/") {
+            ApiFormat::Ollama
+        } else {
+            ApiFormat::OpenAi
+        }
+    }
+}
+
+/// Builds an error body in the shape the client SDK expects.
+///
+/// `error_type` uses Anthropic's vocabulary (`authentication_error`, `permission_error`,
+/// `rate_limit_error`, `billing_error`, ...); it is mapped onto OpenAI's `type`/`code` pair when needed.
+pub fn error_response(
+    format: ApiFormat,
+    status: StatusCode,
+    error_type: &str,
+    message: &str,
+) -> Response {
+    let body = match format {
+        ApiFormat::Anthropic => json!({
+            "type": "error",
+            "error": {
+                "type": error_type,
+                "message": message
+            }
+        }),
+        ApiFormat::Ollama => json!({ "error": message }),
+        ApiFormat::OpenAi => {
+            let (openai_type, code) = match error_type {
+                "authentication_error" => ("invalid_request_error", Some("invalid_api_key")),
+                "permission_error" => ("invalid_request_error", Some("model_not_allowed")),
+                "rate_limit_error" => ("rate_limit_exceeded", Some("rate_limit_exceeded")),
+                "not_found_error" => ("invalid_request_error", Some("not_found")),
+                "billing_error" => ("insufficient_quota", Some("insufficient_quota")),
+                "invalid_request_error" => ("invalid_request_error", None),
+                other => (other, None),
+            };
+            json!({
+                "error": {
+                    "message": message,
+                    "type": openai_type,
+                    "param": null,
+                    "code": code
+                }
+            })
+        }
+    };
+    (status, Json(body)).into_response()
+}
+
+/// 429 response carrying `retry-after` and the rate-limit headers of the API surface in use.
+pub fn rate_limited_response(format: ApiFormat, limited: &RateLimited) -> Response {
+    let mut response = error_response(
+        format,
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limit_error",
+        "Rate limit exceeded for this API key",
+    );
+
+    let headers = response.headers_mut();
+    let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    headers.insert("retry-after", HeaderValue::from(retry_after));
+    insert_bucket_headers(headers, format, "requests", limited.status.requests);
+    insert_bucket_headers(headers, format, "tokens", limited.status.tokens);
+    response
+}
+
+fn insert_bucket_headers(
+    headers: &mut HeaderMap,
+    format: ApiFormat,
+    kind: &str,
+    bucket: Option<BucketSnapshot>,
+) {
+    let Some(bucket) = bucket else {
+        return;
+    };
+
+    let (names, reset) = match format {
+        // anthropic-ratelimit-requests-limit, ... with an RFC 3339 reset time
+        ApiFormat::Anthropic => (
+            ["limit", "remaining", "reset"].map(|s| format!("anthropic-ratelimit-{}-{}", kind, s)),
+            reset_timestamp(bucket.reset),
+        ),
+        // x-ratelimit-limit-requests, ... with a relative reset like "1.5s"
+        ApiFormat::OpenAi | ApiFormat::Ollama => (
+            ["limit", "remaining", "reset"].map(|s| format!("x-ratelimit-{}-{}", s, kind)),
+            relative_reset(bucket.reset),
+        ),
+    };
+
+    let values = [
+        bucket.limit.to_string(),
+        bucket.remaining.to_string(),
+        reset,
+    ];
+    for (name, value) in names.iter().zip(values) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+fn relative_reset(after: Duration) -> String {
+    if after < Duration::from_secs(1) {
+        return format!("{}ms", after.as_millis());
+    }
+    let secs = format!("{:.3}", after.as_secs_f64());
+    format!("{}s", secs.trim_end_matches('0').trim_end_matches('.'))
+}
+
+fn reset_timestamp(after: Duration) -> String {
+    let reset = Utc::now() + chrono::Duration::from_std(after).unwrap_or_default();
+    reset.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::state::AppState;
+
+/// Value of the `route` label for a request path.
+fn route_label(path: &str) -> &'static str {
+    if path.starts_with("/v1/messages/batches")
+        || path.starts_with("/v1/batches")
+        || path.starts_with("/v1/files")
+    {
+        "batches"
+    } else if path.starts_with("/v1/messages") {
+        "anthropic"
+    } else if path.starts_with("/v1/chat/completions") {
+        "openai"
+    } else if path.starts_with("/v1/responses") {
+        "responses"
+    } else if path.starts_with("/v1/models") {
+        "models"
+    } else if path.starts_with("/v1/completions") {
+        "completions"
+    } else if path.starts_with("/v1/embeddings") {
+        "embeddings"
+    } else if path.starts_with("/api/") {
+        "ollama"
+    } else {
+        "other"
+    }
+}
+
+/// Middleware that tracks every API request and hands the tracker to the handler.
+pub async fn track(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let tracker = state.metrics.track(route_label(request.uri().path()));
+    request.extensions_mut().insert(tracker.clone());
+    let response = next.run(request).await;
+    tracker.respond(response.status().as_u16());
+    response
+}
+
+/// `GET /metrics` in the Prometheus text format.
+pub async fn render(State(state): State<AppState>) -> Response {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
+        state.metrics.render(),
+    )
+        .into_response()
+}
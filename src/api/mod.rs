@@ -1,2 +1,6 @@
+pub mod auth;
+pub mod errors;
+pub mod metrics;
 pub mod routes;
+pub mod state;
 pub mod transformers;
@@ -0,0 +1,49 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::api::state::AppState;
+use crate::core::accounting::UsageTotals;
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// Only this client's usage.
+    client: Option<String>,
+    /// First day included (UTC, `YYYY-MM-DD`).
+    from: Option<NaiveDate>,
+    /// Last day included (UTC, `YYYY-MM-DD`).
+    to: Option<NaiveDate>,
+}
+
+/// Aggregated token usage and estimated cost per client and model.
+pub async fn usage(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Json<serde_json::Value> {
+    let rows = state
+        .ledger
+        .summary(query.client.as_deref(), query.from, query.to);
+
+    let mut total = UsageTotals::default();
+    let data: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|(client, route, totals)| {
+            total.merge(totals);
+            let mut row = serde_json::to_value(totals).unwrap_or_default();
+            row["client"] = serde_json::json!(client);
+            row["route"] = serde_json::json!(route);
+            row
+        })
+        .collect();
+
+    Json(serde_json::json!({
+        "object": "list",
+        "from": query.from,
+        "to": query.to,
+        "data": data,
+        "total": total,
+    }))
+}
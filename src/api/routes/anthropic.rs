@@ -1,19 +1,28 @@
 use axum::{
-    body::Body,
-    extract::State,
+    extract::{Extension, State},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use futures::StreamExt;
-use tokio_stream::wrappers::ReceiverStream;
 use tracing::{error, info};
 
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::routes::upstream;
+use crate::api::state::AppState;
 use crate::api::transformers::{anthropic_to_openai, openai_to_anthropic};
-use crate::core::OpenAiClient;
+use crate::core::metrics::RequestTracker;
+use crate::core::tokenizer;
+use crate::core::usage::{Usage, UsageScope};
+
+/// Largest `/v1/messages` body accepted, matching Anthropic's request size limit; tool result
+/// images and PDFs easily outgrow axum's 2 MB default.
+pub const MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
 
 pub async fn messages(
-    State(client): State<OpenAiClient>,
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Extension(tracker): Extension<RequestTracker>,
     Json(body): Json<serde_json::Value>,
 ) -> Response {
     let model = body
@@ -24,155 +33,130 @@ pub async fn messages(
         .get("stream")
         .and_then(|s| s.as_bool())
         .unwrap_or(false);
+    let route = state.router.resolve(model);
+    if let Err(rejection) = state.admit(&identity, model, &route, ApiFormat::Anthropic) {
+        return rejection;
+    }
+    if let Some(url) = anthropic_to_openai::url_document(&body) {
+        return error_response(
+            ApiFormat::Anthropic,
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!(
+                "Document URL sources are not supported, send the PDF as base64 instead: {}",
+                url
+            ),
+        );
+    }
+
+    tracker.set_target(&route);
     info!(
         model = model,
+        client = %identity.name,
         stream = is_stream,
+        provider = %route.primary().provider,
+        upstream_model = %route.primary().model,
         "Anthropic messages request"
     );
 
     // Transform Anthropic request → OpenAI format
-    let openai_body = anthropic_to_openai::transform_request(&body);
+    let openai_body = anthropic_to_openai::transform_request(&body, &state.signer);
 
-    let response = match client.chat_completion(openai_body).await {
-        Ok(r) => r,
-        Err(e) => {
-            error!(error = %e, "OpenAI API request failed");
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(serde_json::json!({
-                    "type": "error",
-                    "error": {
-                        "type": "api_error",
-                        "message": format!("OpenAI API error: {}", e)
-                    }
-                })),
-            )
-                .into_response();
-        }
+    let response = match state.client.chat_completion(&route, openai_body).await {
+        Ok(r) => r.keep_reasoning_signatures(),
+        Err(e) => return upstream::request_failed(ApiFormat::Anthropic, &tracker, &e),
     };
 
+    let upstream = response.provider().to_string();
     let status = response.status();
-
+    tracker.set_upstream(&upstream);
+    tracker.upstream_status(Some(status.as_u16()));
     if !status.is_success() {
-        let status_code = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
-        let body_text = response.text().await.unwrap_or_default();
-        error!(status = %status_code, body = %body_text, "OpenAI API returned error");
-
-        // Try to parse and re-format as Anthropic error
-        return (
-            status_code,
-            Json(serde_json::json!({
-                "type": "error",
-                "error": {
-                    "type": "api_error",
-                    "message": body_text
-                }
-            })),
-        )
-            .into_response();
+        return upstream::error_reply(ApiFormat::Anthropic, response).await;
     }
+    let scope = UsageScope {
+        client: identity.name.clone(),
+        model: model.to_string(),
+        route: route.label().to_string(),
+        provider: upstream.clone(),
+        upstream_model: response.model().to_string(),
+    };
+
+    info!(upstream = %upstream, "Anthropic messages served");
 
     if is_stream {
         // Streaming: transform OpenAI SSE → Anthropic SSE
-        let model_owned = model.to_string();
-        let byte_stream = OpenAiClient::stream_response(response);
-
-        let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, std::convert::Infallible>>(128);
-
-        tokio::spawn(async move {
-            let mut transformer = openai_to_anthropic::StreamTransformer::new(&model_owned);
-            let mut buffer = String::with_capacity(8192);
-
-            // Send message_start
-            let start = transformer.start_event();
-            if tx.send(Ok(start)).await.is_err() {
-                return;
-            }
-
-            let mut stream = byte_stream;
-            while let Some(chunk_result) = stream.next().await {
-                let chunk = match chunk_result {
-                    Ok(c) => c,
-                    Err(e) => {
-                        error!(error = %e, "Stream read error");
-                        break;
-                    }
-                };
-
-                let text = String::from_utf8_lossy(&chunk);
-                buffer.push_str(&text);
-
-                // Process complete SSE lines
-                while let Some(pos) = buffer.find("\n\n") {
-                    let line = buffer.drain(..pos).collect::<String>();
-                    buffer.drain(..2); // Remove "\n\n"
-
-                    for l in line.lines() {
-                        if let Some(data) = l.strip_prefix("data: ") {
-                            let events = transformer.process_chunk(data.trim());
-                            for event in events {
-                                if tx.send(Ok(event)).await.is_err() {
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Process any remaining buffer
-            if !buffer.is_empty() {
-                for l in buffer.lines() {
-                    if let Some(data) = l.strip_prefix("data: ") {
-                        let events = transformer.process_chunk(data.trim());
-                        for event in events {
-                            if tx.send(Ok(event)).await.is_err() {
-                                return;
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Always send final events to ensure proper stream termination
-            let final_events = transformer.finish();
-            for event in final_events {
-                if tx.send(Ok(event)).await.is_err() {
-                    return;
-                }
-            }
-        });
-
-        let stream = ReceiverStream::new(rx);
-        let body = Body::from_stream(stream);
-
-        Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "text/event-stream")
-            .header("Cache-Control", "no-cache")
-            .header("Connection", "keep-alive")
-            .body(body)
-            .unwrap()
+        let transformer = openai_to_anthropic::StreamTransformer::new(model, state.signer.clone());
+        let body = upstream::translate_stream(state, scope, tracker, response, transformer);
+        upstream::streaming("text/event-stream", &upstream, body)
     } else {
-        // Non-streaming: transform response
-        let body_text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                error!(error = %e, "Failed to read OpenAI response");
-                return (StatusCode::BAD_GATEWAY, "Failed to read response").into_response();
-            }
-        };
+        tracker.first_byte();
 
-        let openai_response: serde_json::Value = match serde_json::from_str(&body_text) {
+        // Non-streaming: transform response
+        let openai_response = match upstream::read_json(ApiFormat::Anthropic, response).await {
             Ok(v) => v,
-            Err(e) => {
-                error!(error = %e, body = %body_text, "Failed to parse OpenAI response");
-                return (StatusCode::BAD_GATEWAY, "Failed to parse response").into_response();
-            }
+            Err(rejection) => return rejection,
         };
 
-        let anthropic_response = openai_to_anthropic::transform_response(&openai_response, model);
+        let cost = openai_response
+            .get("usage")
+            .map(Usage::from_openai)
+            .and_then(|usage| {
+                tracker.record_tokens(&usage);
+                state.record_usage(&scope, usage)
+            });
 
-        Json(anthropic_response).into_response()
+        let anthropic_response =
+            openai_to_anthropic::transform_response(&openai_response, model, &state.signer);
+
+        upstream::completed(&state, &upstream, anthropic_response.to_string(), cost)
     }
 }
+
+/// Counts prompt tokens locally, without contacting the upstream.
+pub async fn count_tokens(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or("unknown");
+    let route = state.router.resolve(model);
+    if let Err(rejection) = AppState::check_model(&identity, model, ApiFormat::Anthropic) {
+        return rejection;
+    }
+    if let Err(rejection) = state.check_rate(&identity, model, &route, ApiFormat::Anthropic) {
+        return rejection;
+    }
+
+    // Counting is CPU-bound and bodies may be large, so it stays off the async workers
+    let openai_body = anthropic_to_openai::transform_request(&body, &state.signer);
+    let counter = state.tokenizer.clone();
+    let input_tokens = match tokio::task::spawn_blocking(move || {
+        tokenizer::count_chat_request(counter.as_ref(), &openai_body)
+    })
+    .await
+    {
+        Ok(input_tokens) => input_tokens,
+        Err(e) => {
+            error!(error = %e, "Token counting failed");
+            return error_response(
+                ApiFormat::Anthropic,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "api_error",
+                "Token counting failed",
+            );
+        }
+    };
+    info!(
+        model = model,
+        client = %identity.name,
+        tokenizer = state.tokenizer.name(),
+        input_tokens,
+        "Anthropic count_tokens request"
+    );
+
+    Json(serde_json::json!({ "input_tokens": input_tokens })).into_response()
+}
@@ -0,0 +1,510 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::routes::files::owned_file;
+use crate::api::routes::openai;
+use crate::api::state::AppState;
+use crate::core::batches::{Batch, BatchStatus, COMPLETION_WINDOW};
+
+/// The endpoint batch lines may target.
+const CHAT_COMPLETIONS: &str = "/v1/chat/completions";
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    limit: Option<usize>,
+    after: Option<String>,
+}
+
+/// `POST /v1/batches`: queues the requests of an uploaded JSONL file.
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Json(body): Json<Value>,
+) -> Response {
+    let endpoint = body.get("endpoint").and_then(|e| e.as_str());
+    if endpoint != Some(CHAT_COMPLETIONS) {
+        return bad_request(&format!(
+            "endpoint: only {} is supported by this proxy",
+            CHAT_COMPLETIONS
+        ));
+    }
+    if body.get("completion_window").and_then(|w| w.as_str()) != Some(COMPLETION_WINDOW) {
+        return bad_request(&format!(
+            "completion_window: must be '{}'",
+            COMPLETION_WINDOW
+        ));
+    }
+    let Some(input_file_id) = body.get("input_file_id").and_then(|f| f.as_str()) else {
+        return bad_request("input_file_id: a file id is required");
+    };
+    match owned_file(&state, &identity, input_file_id) {
+        Ok(file) if file.purpose == "batch" => {}
+        Ok(_) => return bad_request("input_file_id: the file must have purpose 'batch'"),
+        Err(rejection) => return rejection,
+    }
+
+    let metadata = body.get("metadata").filter(|m| !m.is_null()).cloned();
+    let batch =
+        match state
+            .batches
+            .create(&identity.name, input_file_id, CHAT_COMPLETIONS, metadata)
+        {
+            Ok(batch) => batch,
+            Err(e) => {
+                error!(error = %e, "Failed to store batch");
+                return error_response(
+                    ApiFormat::OpenAi,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "api_error",
+                    "Failed to store batch",
+                );
+            }
+        };
+    info!(
+        batch = %batch.id,
+        client = %identity.name,
+        input_file = input_file_id,
+        "Batch created"
+    );
+
+    spawn(state, batch.id.clone());
+    Json(render(&batch)).into_response()
+}
+
+/// `GET /v1/batches`, most recent first.
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    let batches = state.batches.list(&identity.name);
+    let start = query
+        .after
+        .as_ref()
+        .and_then(|after| batches.iter().position(|b| b.id == *after))
+        .map_or(0, |p| p + 1);
+    let end = (start + query.limit.unwrap_or(20).clamp(1, 100)).min(batches.len());
+    let page = &batches[start..end];
+
+    Json(json!({
+        "object": "list",
+        "data": page.iter().map(render).collect::<Vec<_>>(),
+        "first_id": page.first().map(|b| &b.id),
+        "last_id": page.last().map(|b| &b.id),
+        "has_more": end < batches.len(),
+    }))
+    .into_response()
+}
+
+/// `GET /v1/batches/{id}`.
+pub async fn retrieve(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    match owned_batch(&state, &identity, &id) {
+        Ok(batch) => Json(render(&batch)).into_response(),
+        Err(rejection) => rejection,
+    }
+}
+
+/// `POST /v1/batches/{id}/cancel`: requests not yet sent end up in the error file.
+pub async fn cancel(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    let batch = match owned_batch(&state, &identity, &id) {
+        Ok(batch) => batch,
+        Err(rejection) => return rejection,
+    };
+    if !matches!(
+        batch.status,
+        BatchStatus::Validating | BatchStatus::InProgress
+    ) {
+        return bad_request(&format!(
+            "Cannot cancel a batch with status '{}'",
+            json!(batch.status).as_str().unwrap_or_default()
+        ));
+    }
+
+    let batch = state.batches.update(&id, |b| {
+        b.status = BatchStatus::Cancelling;
+        b.cancelling_at = Some(Utc::now().timestamp());
+    });
+    info!(batch = %id, client = %identity.name, "Batch cancel requested");
+    match batch {
+        Some(batch) => Json(render(&batch)).into_response(),
+        None => not_found(&id),
+    }
+}
+
+/// Restarts batches a previous run of the proxy left unfinished.
+pub fn resume(state: &AppState) {
+    for batch in state.batches.unfinished() {
+        info!(batch = %batch.id, "Resuming batch");
+        spawn(state.clone(), batch.id);
+    }
+}
+
+fn spawn(state: AppState, id: String) {
+    tokio::spawn(async move {
+        run(&state, &id).await;
+    });
+}
+
+/// Validates the input file, runs every request without an output line through the
+/// `/v1/chat/completions` handler a few at a time, then publishes the output files.
+async fn run(state: &AppState, id: &str) {
+    let Some(batch) = state.batches.get(id) else {
+        return;
+    };
+
+    if batch.status != BatchStatus::Finalizing {
+        let requests = match read_input(state, &batch) {
+            Ok(requests) => requests,
+            Err(errors) => {
+                warn!(batch = %id, errors = errors.len(), "Batch input file is invalid");
+                state.batches.update(id, |b| {
+                    b.status = BatchStatus::Failed;
+                    b.failed_at = Some(Utc::now().timestamp());
+                    b.errors = Some(json!({ "object": "list", "data": errors }));
+                });
+                return;
+            }
+        };
+        state.batches.update(id, |b| {
+            b.request_counts.total = requests.len() as u64;
+            if b.status == BatchStatus::Validating {
+                b.status = BatchStatus::InProgress;
+                b.in_progress_at = Some(Utc::now().timestamp());
+            }
+        });
+
+        let completed = state.batches.completed_ids(id);
+        // The key's current model list, budget and limits apply to every request
+        let identity = state.client_keys.identity_by_name(&batch.client);
+        futures::stream::iter(
+            requests
+                .into_iter()
+                .filter(|(custom_id, _)| !completed.contains(custom_id)),
+        )
+        .for_each_concurrent(state.batches.concurrency(), |(custom_id, body)| {
+            let identity = identity.clone();
+            async move {
+                let (succeeded, line) = execute(state, id, identity, &custom_id, body).await;
+                state.batches.record_result(id, &line, succeeded);
+            }
+        })
+        .await;
+    }
+
+    finalize(state, id);
+}
+
+/// Turns the output and error lines into files and settles the final status.
+fn finalize(state: &AppState, id: &str) {
+    let Some(batch) = state.batches.update(id, |b| {
+        if b.status == BatchStatus::InProgress {
+            b.status = BatchStatus::Finalizing;
+            b.finalizing_at = Some(Utc::now().timestamp());
+        }
+    }) else {
+        return;
+    };
+
+    let publish = |path: std::path::PathBuf, kind: &str| {
+        if !path.exists() {
+            return None;
+        }
+        let filename = format!("{}_{}.jsonl", id, kind);
+        match state
+            .files
+            .import(&batch.client, &filename, "batch_output", &path)
+        {
+            Ok(file) => Some(file.id),
+            Err(e) => {
+                error!(error = %e, batch = %id, "Failed to store batch {} file", kind);
+                None
+            }
+        }
+    };
+    let output_file_id = publish(state.batches.output_path(id), "output");
+    let error_file_id = publish(state.batches.error_path(id), "error");
+
+    let Some(batch) = state.batches.update(id, |b| {
+        let now = Some(Utc::now().timestamp());
+        b.output_file_id = output_file_id.or(b.output_file_id.take());
+        b.error_file_id = error_file_id.or(b.error_file_id.take());
+        if b.status == BatchStatus::Cancelling {
+            b.status = BatchStatus::Cancelled;
+            b.cancelled_at = now;
+        } else if b.is_expired() {
+            b.status = BatchStatus::Expired;
+            b.expired_at = now;
+        } else {
+            b.status = BatchStatus::Completed;
+            b.completed_at = now;
+        }
+    }) else {
+        return;
+    };
+    info!(
+        batch = %id,
+        status = ?batch.status,
+        completed = batch.request_counts.completed,
+        failed = batch.request_counts.failed,
+        "Batch ended"
+    );
+}
+
+/// Parses the input file into `(custom_id, body)` pairs, or OpenAI-style line errors.
+fn read_input(state: &AppState, batch: &Batch) -> Result<Vec<(String, Value)>, Vec<Value>> {
+    let line_error = |code: &str, message: &str, line: Option<usize>| json!({ "code": code, "message": message, "param": null, "line": line });
+    let content = match state.files.content(&batch.input_file_id) {
+        Ok(content) => content,
+        Err(_) => {
+            return Err(vec![line_error(
+                "file_not_found",
+                &format!("Input file {} could not be read", batch.input_file_id),
+                None,
+            )]);
+        }
+    };
+
+    let mut requests = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    for (i, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = Some(i + 1);
+        let Ok(request) = serde_json::from_str::<Value>(line) else {
+            errors.push(line_error(
+                "invalid_json_line",
+                "This line is not parseable as valid JSON.",
+                number,
+            ));
+            continue;
+        };
+        let Some(custom_id) = request.get("custom_id").and_then(|c| c.as_str()) else {
+            errors.push(line_error(
+                "missing_required_parameter",
+                "custom_id is required",
+                number,
+            ));
+            continue;
+        };
+        if !seen.insert(custom_id.to_string()) {
+            errors.push(line_error(
+                "duplicate_custom_id",
+                &format!("custom_id '{}' is used more than once", custom_id),
+                number,
+            ));
+            continue;
+        }
+        if request
+            .get("method")
+            .and_then(|m| m.as_str())
+            .is_some_and(|m| m != "POST")
+        {
+            errors.push(line_error("invalid_method", "method must be POST", number));
+            continue;
+        }
+        if request.get("url").and_then(|u| u.as_str()) != Some(batch.endpoint.as_str()) {
+            errors.push(line_error(
+                "mismatched_endpoint",
+                &format!("url must match the batch endpoint {}", batch.endpoint),
+                number,
+            ));
+            continue;
+        }
+        let Some(body) = request.get("body").filter(|b| b.is_object()) else {
+            errors.push(line_error(
+                "missing_required_parameter",
+                "body must be an object",
+                number,
+            ));
+            continue;
+        };
+        requests.push((custom_id.to_string(), body.clone()));
+    }
+
+    if requests.is_empty() && errors.is_empty() {
+        errors.push(line_error(
+            "empty_file",
+            "The input file contains no requests",
+            None,
+        ));
+    }
+    if errors.is_empty() {
+        Ok(requests)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Runs one request, retrying retryable statuses per the batch retry policy.
+///
+/// Returns whether it succeeded and its line for the output or error file.
+async fn execute(
+    state: &AppState,
+    id: &str,
+    identity: Option<ClientIdentity>,
+    custom_id: &str,
+    mut body: Value,
+) -> (bool, Value) {
+    let Some(identity) = identity else {
+        return (
+            false,
+            error_line(
+                custom_id,
+                "invalid_api_key",
+                "The API key that created this batch is no longer valid.",
+            ),
+        );
+    };
+    body["stream"] = json!(false);
+    let policy = state.batches.retry();
+    let mut attempt = 1;
+
+    loop {
+        let batch = state.batches.get(id);
+        if batch
+            .as_ref()
+            .is_none_or(|b| b.status == BatchStatus::Cancelling)
+        {
+            return (
+                false,
+                error_line(
+                    custom_id,
+                    "batch_cancelled",
+                    "This request was not executed because the batch was cancelled.",
+                ),
+            );
+        }
+        if batch.is_some_and(|b| b.is_expired()) {
+            return (
+                false,
+                error_line(
+                    custom_id,
+                    "batch_expired",
+                    "This request could not be executed before the completion window expired.",
+                ),
+            );
+        }
+
+        let tracker = state.metrics.track("batches");
+        let response = openai::chat_completions(
+            State(state.clone()),
+            Extension(identity.clone()),
+            Extension(tracker.clone()),
+            Json(body.clone()),
+        )
+        .await;
+        let status = response.status();
+        tracker.respond(status.as_u16());
+
+        if attempt < policy.max_attempts() && policy.is_retryable(status) {
+            let delay = policy.delay(attempt, Some((status, response.headers())));
+            warn!(
+                batch = %id,
+                custom_id,
+                status = status.as_u16(),
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "Retrying batch request"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+            continue;
+        }
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap_or_default();
+        let response_body = serde_json::from_slice::<Value>(&bytes).unwrap_or_else(|_| {
+            json!({
+                "error": {
+                    "message": String::from_utf8_lossy(&bytes),
+                    "type": "api_error"
+                }
+            })
+        });
+        return (
+            status.is_success(),
+            json!({
+                "id": format!("batch_req_{}", Uuid::new_v4().simple()),
+                "custom_id": custom_id,
+                "response": {
+                    "status_code": status.as_u16(),
+                    "request_id": format!("req_{}", Uuid::new_v4().simple()),
+                    "body": response_body
+                },
+                "error": null
+            }),
+        );
+    }
+}
+
+/// Error-file line for a request that never got a response.
+fn error_line(custom_id: &str, code: &str, message: &str) -> Value {
+    json!({
+        "id": format!("batch_req_{}", Uuid::new_v4().simple()),
+        "custom_id": custom_id,
+        "response": null,
+        "error": { "code": code, "message": message }
+    })
+}
+
+/// Looks up a batch created with the caller's key; other keys' batches are not found.
+#[allow(clippy::result_large_err)]
+fn owned_batch(state: &AppState, identity: &ClientIdentity, id: &str) -> Result<Batch, Response> {
+    state
+        .batches
+        .get(id)
+        .filter(|batch| batch.client == identity.name)
+        .ok_or_else(|| not_found(id))
+}
+
+fn not_found(id: &str) -> Response {
+    error_response(
+        ApiFormat::OpenAi,
+        StatusCode::NOT_FOUND,
+        "not_found_error",
+        &format!("No such Batch object: {}", id),
+    )
+}
+
+fn bad_request(message: &str) -> Response {
+    error_response(
+        ApiFormat::OpenAi,
+        StatusCode::BAD_REQUEST,
+        "invalid_request_error",
+        message,
+    )
+}
+
+/// Renders a batch as OpenAI's `batch` object.
+fn render(batch: &Batch) -> Value {
+    let mut value = serde_json::to_value(batch).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        object.remove("client");
+        object.insert("object".to_string(), json!("batch"));
+    }
+    value
+}
@@ -0,0 +1,218 @@
+use axum::{
+    body::Body,
+    extract::{Extension, Multipart, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{error, info};
+
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::state::AppState;
+use crate::core::files::StoredFile;
+
+/// Largest upload accepted by `/v1/files`, matching OpenAI's limit for batch input.
+pub const MAX_UPLOAD_BYTES: usize = 200 * 1024 * 1024;
+
+/// Only batch input is useful to this proxy; other purposes are rejected.
+const BATCH_PURPOSE: &str = "batch";
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    purpose: Option<String>,
+    limit: Option<usize>,
+    after: Option<String>,
+    /// `asc` or `desc` (default) by creation time.
+    order: Option<String>,
+}
+
+/// `POST /v1/files`: a multipart upload with `file` and `purpose` fields.
+pub async fn upload(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    mut multipart: Multipart,
+) -> Response {
+    let mut purpose = None;
+    let mut upload = None;
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) => match field.name() {
+                Some("purpose") => purpose = field.text().await.ok(),
+                Some("file") => {
+                    let filename = field.file_name().unwrap_or("upload.jsonl").to_string();
+                    match field.bytes().await {
+                        Ok(bytes) => upload = Some((filename, bytes)),
+                        Err(e) => return bad_request(&format!("Failed to read file: {}", e)),
+                    }
+                }
+                _ => {}
+            },
+            Ok(None) => break,
+            Err(e) => return bad_request(&format!("Invalid multipart body: {}", e)),
+        }
+    }
+
+    let Some((filename, content)) = upload else {
+        return bad_request("file: a file is required");
+    };
+    match purpose.as_deref() {
+        Some(BATCH_PURPOSE) => {}
+        Some(other) => {
+            return bad_request(&format!(
+                "purpose: '{}' is not supported by this proxy, only '{}'",
+                other, BATCH_PURPOSE
+            ));
+        }
+        None => return bad_request("purpose: a purpose is required"),
+    }
+
+    match state
+        .files
+        .create(&identity.name, &filename, BATCH_PURPOSE, &content)
+    {
+        Ok(file) => {
+            info!(file = %file.id, client = %identity.name, bytes = file.bytes, "File uploaded");
+            Json(render(&file)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to store uploaded file");
+            error_response(
+                ApiFormat::OpenAi,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "api_error",
+                "Failed to store file",
+            )
+        }
+    }
+}
+
+/// `GET /v1/files`.
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    let mut files = state.files.list(&identity.name);
+    if query.order.as_deref() == Some("asc") {
+        files.reverse();
+    }
+    files.retain(|f| query.purpose.as_ref().is_none_or(|p| *p == f.purpose));
+
+    let start = query
+        .after
+        .as_ref()
+        .and_then(|after| files.iter().position(|f| f.id == *after))
+        .map_or(0, |p| p + 1);
+    let end = (start + query.limit.unwrap_or(10_000).clamp(1, 10_000)).min(files.len());
+    let page = &files[start..end];
+
+    Json(json!({
+        "object": "list",
+        "data": page.iter().map(render).collect::<Vec<_>>(),
+        "first_id": page.first().map(|f| &f.id),
+        "last_id": page.last().map(|f| &f.id),
+        "has_more": end < files.len(),
+    }))
+    .into_response()
+}
+
+/// `GET /v1/files/{id}`.
+pub async fn retrieve(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    match owned_file(&state, &identity, &id) {
+        Ok(file) => Json(render(&file)).into_response(),
+        Err(rejection) => rejection,
+    }
+}
+
+/// `GET /v1/files/{id}/content`.
+pub async fn content(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(rejection) = owned_file(&state, &identity, &id) {
+        return rejection;
+    }
+    match state.files.content(&id) {
+        Ok(content) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/octet-stream")
+            .body(Body::from(content))
+            .unwrap(),
+        Err(e) => {
+            error!(error = %e, file = %id, "Failed to read stored file");
+            error_response(
+                ApiFormat::OpenAi,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "api_error",
+                "Failed to read file",
+            )
+        }
+    }
+}
+
+/// `DELETE /v1/files/{id}`.
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(rejection) = owned_file(&state, &identity, &id) {
+        return rejection;
+    }
+    state.files.delete(&id);
+    info!(file = %id, client = %identity.name, "File deleted");
+    Json(json!({ "id": id, "object": "file", "deleted": true })).into_response()
+}
+
+/// Looks up a file owned by the caller's key; other keys' files are not found.
+#[allow(clippy::result_large_err)]
+pub(crate) fn owned_file(
+    state: &AppState,
+    identity: &ClientIdentity,
+    id: &str,
+) -> Result<StoredFile, Response> {
+    state
+        .files
+        .get(id)
+        .filter(|file| file.client == identity.name)
+        .ok_or_else(|| {
+            error_response(
+                ApiFormat::OpenAi,
+                StatusCode::NOT_FOUND,
+                "not_found_error",
+                &format!("No such File object: {}", id),
+            )
+        })
+}
+
+fn bad_request(message: &str) -> Response {
+    error_response(
+        ApiFormat::OpenAi,
+        StatusCode::BAD_REQUEST,
+        "invalid_request_error",
+        message,
+    )
+}
+
+/// Renders a stored file as OpenAI's `file` object.
+fn render(file: &StoredFile) -> Value {
+    json!({
+        "id": file.id,
+        "object": "file",
+        "bytes": file.bytes,
+        "created_at": file.created_at,
+        "filename": file.filename,
+        "purpose": file.purpose,
+        "status": "processed",
+        "status_details": null,
+        "expires_at": null,
+    })
+}
@@ -0,0 +1,448 @@
+use axum::{
+    body::Body,
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::routes::anthropic;
+use crate::api::state::AppState;
+use crate::core::message_batches::{BatchRequest, MessageBatch, ProcessingStatus};
+
+/// Anthropic's limit on requests per batch.
+const MAX_BATCH_REQUESTS: usize = 100_000;
+
+/// Largest batch creation body accepted. The body is parsed in memory, several times its
+/// size, so this stays well below Anthropic's 256 MB; larger batches have to be split.
+pub const MAX_BATCH_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    limit: Option<usize>,
+    /// Page of batches created after this one (newer).
+    before_id: Option<String>,
+    /// Page of batches created before this one (older).
+    after_id: Option<String>,
+}
+
+/// `POST /v1/messages/batches`: stores the batch and starts executing it in the background.
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Json(body): Json<Value>,
+) -> Response {
+    let requests = match parse_requests(&body) {
+        Ok(requests) => requests,
+        Err(message) => {
+            return error_response(
+                ApiFormat::Anthropic,
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &message,
+            );
+        }
+    };
+
+    let batch = match state.message_batches.create(&identity.name, &requests) {
+        Ok(batch) => batch,
+        Err(e) => {
+            error!(error = %e, "Failed to store message batch");
+            return error_response(
+                ApiFormat::Anthropic,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "api_error",
+                "Failed to store message batch",
+            );
+        }
+    };
+    info!(
+        batch = %batch.id,
+        client = %identity.name,
+        requests = requests.len(),
+        "Message batch created"
+    );
+
+    spawn(state, batch.id.clone());
+    Json(render(&batch)).into_response()
+}
+
+/// `GET /v1/messages/batches`, most recent first.
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    let batches = state.message_batches.list(&identity.name);
+    let limit = query.limit.unwrap_or(20).clamp(1, 1000);
+    let position = |id: &str| batches.iter().position(|b| b.id == id);
+
+    let (start, end) = match (&query.after_id, &query.before_id) {
+        (Some(after_id), _) => {
+            let start = position(after_id).map_or(batches.len(), |p| p + 1);
+            (start, (start + limit).min(batches.len()))
+        }
+        (None, Some(before_id)) => {
+            let end = position(before_id).unwrap_or(0);
+            (end.saturating_sub(limit), end)
+        }
+        (None, None) => (0, limit.min(batches.len())),
+    };
+    let page = &batches[start..end];
+    let has_more = if query.before_id.is_some() && query.after_id.is_none() {
+        start > 0
+    } else {
+        end < batches.len()
+    };
+
+    Json(json!({
+        "data": page.iter().map(render).collect::<Vec<_>>(),
+        "has_more": has_more,
+        "first_id": page.first().map(|b| &b.id),
+        "last_id": page.last().map(|b| &b.id),
+    }))
+    .into_response()
+}
+
+/// `GET /v1/messages/batches/{id}`.
+pub async fn retrieve(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    match owned_batch(&state, &identity, &id) {
+        Ok(batch) => Json(render(&batch)).into_response(),
+        Err(rejection) => rejection,
+    }
+}
+
+/// `POST /v1/messages/batches/{id}/cancel`: requests not yet sent end up `canceled`.
+pub async fn cancel(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(rejection) = owned_batch(&state, &identity, &id) {
+        return rejection;
+    }
+    match state.message_batches.cancel(&id) {
+        Some(batch) => {
+            info!(batch = %id, client = %identity.name, "Message batch cancel requested");
+            Json(render(&batch)).into_response()
+        }
+        None => not_found(&id),
+    }
+}
+
+/// `GET /v1/messages/batches/{id}/results`: one JSON result per line, once the batch has ended.
+pub async fn results(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    let batch = match owned_batch(&state, &identity, &id) {
+        Ok(batch) => batch,
+        Err(rejection) => return rejection,
+    };
+    if batch.processing_status != ProcessingStatus::Ended {
+        return error_response(
+            ApiFormat::Anthropic,
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!("Message batch '{}' has not finished processing", id),
+        );
+    }
+
+    match state.message_batches.results(&id) {
+        Ok(results) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/x-jsonl")
+            .body(Body::from(results))
+            .unwrap(),
+        Err(e) => {
+            error!(error = %e, batch = %id, "Failed to read batch results");
+            error_response(
+                ApiFormat::Anthropic,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "api_error",
+                "Failed to read batch results",
+            )
+        }
+    }
+}
+
+/// `DELETE /v1/messages/batches/{id}`: removes an ended batch and its results.
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Path(id): Path<String>,
+) -> Response {
+    let batch = match owned_batch(&state, &identity, &id) {
+        Ok(batch) => batch,
+        Err(rejection) => return rejection,
+    };
+    if batch.processing_status != ProcessingStatus::Ended {
+        return error_response(
+            ApiFormat::Anthropic,
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!(
+                "Message batch '{}' is still processing; cancel it before deleting",
+                id
+            ),
+        );
+    }
+
+    state.message_batches.delete(&id);
+    info!(batch = %id, client = %identity.name, "Message batch deleted");
+    Json(json!({ "id": id, "type": "message_batch_deleted" })).into_response()
+}
+
+/// Restarts batches a previous run of the proxy left unfinished.
+pub fn resume(state: &AppState) {
+    for batch in state.message_batches.unfinished() {
+        info!(batch = %batch.id, "Resuming message batch");
+        spawn(state.clone(), batch.id);
+    }
+}
+
+fn spawn(state: AppState, id: String) {
+    tokio::spawn(async move {
+        run(&state, &id).await;
+    });
+}
+
+/// Sends every request without a result through the `/v1/messages` handler, a few at a time.
+async fn run(state: &AppState, id: &str) {
+    let Some(batch) = state.message_batches.get(id) else {
+        return;
+    };
+    let requests = match state.message_batches.requests(id) {
+        Ok(requests) => requests,
+        Err(e) => {
+            error!(error = %e, batch = %id, "Failed to read batch requests");
+            state.message_batches.finish(id);
+            return;
+        }
+    };
+    let completed = state.message_batches.completed_ids(id);
+    // The key's current model list, budget and limits apply to every request
+    let identity = state.client_keys.identity_by_name(&batch.client);
+
+    futures::stream::iter(
+        requests
+            .into_iter()
+            .filter(|r| !completed.contains(&r.custom_id)),
+    )
+    .for_each_concurrent(state.message_batches.concurrency(), |request| {
+        let identity = identity.clone();
+        async move {
+            let result = execute(state, id, identity, request.params).await;
+            state
+                .message_batches
+                .record_result(id, &request.custom_id, result);
+        }
+    })
+    .await;
+
+    if let Some(batch) = state.message_batches.finish(id) {
+        let counts = batch.request_counts;
+        info!(
+            batch = %id,
+            succeeded = counts.succeeded,
+            errored = counts.errored,
+            canceled = counts.canceled,
+            expired = counts.expired,
+            "Message batch ended"
+        );
+    }
+}
+
+/// Runs one request and returns its `result` object.
+///
+/// Requests answered with a retryable status are tried again per `batches.retry`, the same
+/// policy OpenAI batches use; a cancel or expiry ends the wait.
+async fn execute(
+    state: &AppState,
+    id: &str,
+    identity: Option<ClientIdentity>,
+    mut params: Value,
+) -> Value {
+    let Some(identity) = identity else {
+        return errored(
+            "authentication_error",
+            "The API key that created this batch is no longer valid",
+        );
+    };
+    params["stream"] = json!(false);
+    let policy = state.message_batches.retry();
+    let mut attempt = 1;
+
+    loop {
+        match state.message_batches.get(id) {
+            Some(batch) if batch.processing_status == ProcessingStatus::Canceling => {
+                return json!({ "type": "canceled" });
+            }
+            Some(batch) if batch.is_expired() => return json!({ "type": "expired" }),
+            Some(_) => {}
+            None => return json!({ "type": "canceled" }),
+        }
+
+        let tracker = state.metrics.track("batches");
+        let response = anthropic::messages(
+            State(state.clone()),
+            Extension(identity.clone()),
+            Extension(tracker.clone()),
+            Json(params.clone()),
+        )
+        .await;
+        let status = response.status();
+        tracker.respond(status.as_u16());
+
+        if attempt < policy.max_attempts() && policy.is_retryable(status) {
+            let delay = policy.delay(attempt, Some((status, response.headers())));
+            warn!(
+                batch = %id,
+                status = status.as_u16(),
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "Retrying message batch request"
+            );
+            // Wait in steps so a cancel is noticed before the full wait is over
+            let deadline = Instant::now() + delay;
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                tokio::time::sleep(left.min(Duration::from_secs(1))).await;
+                if state
+                    .message_batches
+                    .get(id)
+                    .is_none_or(|b| b.processing_status == ProcessingStatus::Canceling)
+                {
+                    break;
+                }
+            }
+            attempt += 1;
+            continue;
+        }
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap_or_default();
+        let parsed = serde_json::from_slice::<Value>(&body).ok();
+        return match parsed {
+            Some(message) if status.is_success() => {
+                json!({ "type": "succeeded", "message": message })
+            }
+            Some(error) if error.get("type").and_then(|t| t.as_str()) == Some("error") => {
+                json!({ "type": "errored", "error": error })
+            }
+            _ => errored("api_error", &String::from_utf8_lossy(&body)),
+        };
+    }
+}
+
+fn errored(error_type: &str, message: &str) -> Value {
+    json!({
+        "type": "errored",
+        "error": {
+            "type": "error",
+            "error": { "type": error_type, "message": message }
+        }
+    })
+}
+
+/// Validates the `requests` list of a create call.
+fn parse_requests(body: &Value) -> Result<Vec<BatchRequest>, String> {
+    let requests = body
+        .get("requests")
+        .and_then(|r| r.as_array())
+        .filter(|r| !r.is_empty())
+        .ok_or("requests: must be a non-empty list")?;
+    if requests.len() > MAX_BATCH_REQUESTS {
+        return Err(format!(
+            "requests: a batch holds at most {} requests",
+            MAX_BATCH_REQUESTS
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut parsed = Vec::with_capacity(requests.len());
+    for (i, request) in requests.iter().enumerate() {
+        let custom_id = request
+            .get("custom_id")
+            .and_then(|c| c.as_str())
+            .filter(|c| {
+                (1..=64).contains(&c.len())
+                    && c.chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+            })
+            .ok_or_else(|| {
+                format!(
+                    "requests.{}.custom_id: must be 1-64 letters, digits, '-' or '_'",
+                    i
+                )
+            })?;
+        if !seen.insert(custom_id) {
+            return Err(format!(
+                "requests.{}.custom_id: '{}' is used more than once",
+                i, custom_id
+            ));
+        }
+        let params = request
+            .get("params")
+            .filter(|p| p.is_object())
+            .ok_or_else(|| format!("requests.{}.params: must be an object", i))?;
+        parsed.push(BatchRequest {
+            custom_id: custom_id.to_string(),
+            params: params.clone(),
+        });
+    }
+    Ok(parsed)
+}
+
+/// Looks up a batch created with the caller's key; other keys' batches are not found.
+#[allow(clippy::result_large_err)]
+fn owned_batch(
+    state: &AppState,
+    identity: &ClientIdentity,
+    id: &str,
+) -> Result<MessageBatch, Response> {
+    state
+        .message_batches
+        .get(id)
+        .filter(|batch| batch.client == identity.name)
+        .ok_or_else(|| not_found(id))
+}
+
+fn not_found(id: &str) -> Response {
+    error_response(
+        ApiFormat::Anthropic,
+        StatusCode::NOT_FOUND,
+        "not_found_error",
+        &format!("Message batch '{}' not found", id),
+    )
+}
+
+/// Renders a batch as Anthropic's `message_batch` object.
+fn render(batch: &MessageBatch) -> Value {
+    let ended = batch.processing_status == ProcessingStatus::Ended;
+    json!({
+        "id": batch.id,
+        "type": "message_batch",
+        "processing_status": batch.processing_status,
+        "request_counts": batch.request_counts,
+        "ended_at": batch.ended_at,
+        "created_at": batch.created_at,
+        "expires_at": batch.expires_at,
+        "archived_at": null,
+        "cancel_initiated_at": batch.cancel_initiated_at,
+        "results_url": ended.then(|| format!("/v1/messages/batches/{}/results", batch.id)),
+    })
+}
@@ -1,2 +1,151 @@
+pub mod admin;
 pub mod anthropic;
+pub mod batches;
+pub mod files;
+pub mod message_batches;
+pub mod models;
+pub mod ollama;
 pub mod openai;
+pub mod passthrough;
+pub mod responses;
+pub mod upstream;
+
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post, MethodRouter};
+
+use crate::api::state::AppState;
+
+/// Every path of the client-facing API with its handlers: the proxy's own endpoints, then
+/// the configured `passthrough` paths.
+///
+/// Panics if a passthrough path would take over one of the proxy's own endpoints.
+pub fn api_routes(passthrough: &[String]) -> Vec<(String, MethodRouter<AppState>)> {
+    let builtin = [
+        // OpenAI-compatible endpoint
+        ("/v1/chat/completions", post(openai::chat_completions)),
+        // OpenAI Responses API, served through chat completions
+        ("/v1/responses", post(responses::responses)),
+        // Model list, in OpenAI or Anthropic format
+        ("/v1/models", get(models::list_models)),
+        // Anthropic-compatible endpoint
+        (
+            "/v1/messages",
+            post(anthropic::messages).layer(DefaultBodyLimit::max(anthropic::MAX_REQUEST_BYTES)),
+        ),
+        (
+            "/v1/messages/count_tokens",
+            post(anthropic::count_tokens)
+                .layer(DefaultBodyLimit::max(anthropic::MAX_REQUEST_BYTES)),
+        ),
+        // Message Batches, executed locally through /v1/messages
+        (
+            "/v1/messages/batches",
+            post(message_batches::create)
+                .layer(DefaultBodyLimit::max(message_batches::MAX_BATCH_BYTES))
+                .get(message_batches::list),
+        ),
+        (
+            "/v1/messages/batches/{id}",
+            get(message_batches::retrieve).delete(message_batches::delete),
+        ),
+        (
+            "/v1/messages/batches/{id}/cancel",
+            post(message_batches::cancel),
+        ),
+        (
+            "/v1/messages/batches/{id}/results",
+            get(message_batches::results),
+        ),
+        // Ollama-native endpoints, served through chat completions
+        ("/api/chat", post(ollama::chat)),
+        ("/api/generate", post(ollama::generate)),
+        ("/api/tags", get(ollama::tags)),
+        // OpenAI Batch API with its file storage, executed locally through chat completions
+        (
+            "/v1/files",
+            post(files::upload)
+                .layer(DefaultBodyLimit::max(files::MAX_UPLOAD_BYTES))
+                .get(files::list),
+        ),
+        ("/v1/files/{id}", get(files::retrieve).delete(files::delete)),
+        ("/v1/files/{id}/content", get(files::content)),
+        ("/v1/batches", post(batches::create).get(batches::list)),
+        ("/v1/batches/{id}", get(batches::retrieve)),
+        ("/v1/batches/{id}/cancel", post(batches::cancel)),
+        // OpenAI endpoints forwarded verbatim to OpenAI-compatible providers
+        ("/v1/completions", forward()),
+        ("/v1/embeddings", forward()),
+    ];
+
+    for path in passthrough {
+        if builtin.iter().any(|(served, _)| overlaps(served, path)) {
+            panic!(
+                "passthrough path '{}' in config.yaml is already served by the proxy",
+                path
+            );
+        }
+    }
+
+    builtin
+        .into_iter()
+        .map(|(path, route)| (path.to_string(), route))
+        .chain(passthrough.iter().map(|path| (path.clone(), forward())))
+        .collect()
+}
+
+fn forward() -> MethodRouter<AppState> {
+    post(passthrough::forward).layer(DefaultBodyLimit::max(passthrough::MAX_REQUEST_BYTES))
+}
+
+/// Whether two route paths can match the same request; a `{param}` segment matches any one.
+fn overlaps(a: &str, b: &str) -> bool {
+    let a: Vec<&str> = a.split('/').collect();
+    let b: Vec<&str> = b.split('/').collect();
+    let is_param = |segment: &str| segment.starts_with('{');
+    a.len() == b.len()
+        && a.iter()
+            .zip(&b)
+            .all(|(x, y)| x == y || is_param(x) || is_param(y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(passthrough: &[&str]) -> Vec<String> {
+        let passthrough: Vec<String> = passthrough.iter().map(|p| p.to_string()).collect();
+        api_routes(&passthrough)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    #[test]
+    fn passthrough_paths_are_added_after_the_builtin_routes() {
+        let paths = paths(&["/v1/rerank", "/v1/audio/transcriptions"]);
+        assert!(paths.contains(&"/v1/chat/completions".to_string()));
+        assert_eq!(
+            &paths[paths.len() - 2..],
+            ["/v1/rerank", "/v1/audio/transcriptions"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "already served")]
+    fn builtin_paths_cannot_be_taken_over() {
+        paths(&["/v1/embeddings"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already served")]
+    fn parameterized_routes_cover_their_segment() {
+        paths(&["/v1/files/latest"]);
+    }
+
+    #[test]
+    fn overlap_is_decided_per_segment() {
+        assert!(overlaps("/v1/batches/{id}/cancel", "/v1/batches/b1/cancel"));
+        assert!(!overlaps("/v1/batches/{id}", "/v1/batches/b1/cancel"));
+        assert!(!overlaps("/v1/files", "/v1/files-archive"));
+    }
+}
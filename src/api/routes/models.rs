@@ -0,0 +1,140 @@
+use axum::{
+    extract::{Extension, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::DateTime;
+use serde::Deserialize;
+use std::collections::HashSet;
+use tracing::info;
+
+use crate::api::auth::ClientIdentity;
+use crate::api::state::AppState;
+
+const ANTHROPIC_DEFAULT_LIMIT: usize = 20;
+const ANTHROPIC_MAX_LIMIT: usize = 1000;
+
+/// One entry of the merged model list.
+pub(crate) struct ModelEntry {
+    pub id: String,
+    pub created: i64,
+    pub owned_by: String,
+}
+
+/// Pagination parameters of Anthropic's `GET /v1/models`; ignored for OpenAI clients.
+#[derive(Debug, Deserialize)]
+pub struct ListModelsQuery {
+    limit: Option<usize>,
+    after_id: Option<String>,
+    before_id: Option<String>,
+}
+
+/// Lists configured aliases and every upstream's models, in OpenAI or Anthropic format.
+///
+/// Requests carrying `anthropic-version` get Anthropic's paginated format.
+pub async fn list_models(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    headers: HeaderMap,
+    Query(query): Query<ListModelsQuery>,
+) -> Json<serde_json::Value> {
+    let models = collect_models(&state, &identity);
+
+    if headers.contains_key("anthropic-version") {
+        Json(anthropic_page(&models, &query))
+    } else {
+        Json(serde_json::json!({
+            "object": "list",
+            "data": models
+                .iter()
+                .map(|m| serde_json::json!({
+                    "id": m.id,
+                    "object": "model",
+                    "created": m.created,
+                    "owned_by": m.owned_by,
+                }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+}
+
+/// Aliases followed by every upstream's models, deduplicated and filtered for the client.
+pub(crate) fn collect_models(state: &AppState, identity: &ClientIdentity) -> Vec<ModelEntry> {
+    let mut seen = HashSet::new();
+    let mut models = Vec::new();
+
+    // Aliases first, so clients see the names the proxy routes explicitly
+    for (alias, target) in state.router.aliases() {
+        if seen.insert(alias.clone()) {
+            models.push(ModelEntry {
+                id: alias,
+                created: 0,
+                owned_by: target.provider,
+            });
+        }
+    }
+
+    // The health monitor refreshes these lists every probe interval, so listing models
+    // costs no upstream calls
+    for (provider, entries) in state.health.models() {
+        for entry in entries {
+            let Some(id) = entry.get("id").and_then(|id| id.as_str()) else {
+                continue;
+            };
+            if seen.insert(id.to_string()) {
+                models.push(ModelEntry {
+                    id: id.to_string(),
+                    created: entry.get("created").and_then(|c| c.as_i64()).unwrap_or(0),
+                    owned_by: provider.clone(),
+                });
+            }
+        }
+    }
+
+    models.retain(|m| identity.allows_model(&m.id));
+    info!(client = %identity.name, models = models.len(), "Models listed");
+    models
+}
+
+fn anthropic_page(models: &[ModelEntry], query: &ListModelsQuery) -> serde_json::Value {
+    let limit = query
+        .limit
+        .unwrap_or(ANTHROPIC_DEFAULT_LIMIT)
+        .clamp(1, ANTHROPIC_MAX_LIMIT);
+    let position = |id: &str| models.iter().position(|m| m.id == id);
+
+    let (start, end) = match (&query.after_id, &query.before_id) {
+        (_, Some(before)) => {
+            let end = position(before).unwrap_or(0);
+            (end.saturating_sub(limit), end)
+        }
+        (Some(after), None) => {
+            let start = position(after).map_or(models.len(), |i| i + 1);
+            (start, (start + limit).min(models.len()))
+        }
+        (None, None) => (0, limit.min(models.len())),
+    };
+    let page = &models[start..end];
+    let has_more = if query.before_id.is_some() {
+        start > 0
+    } else {
+        end < models.len()
+    };
+
+    serde_json::json!({
+        "data": page
+            .iter()
+            .map(|m| serde_json::json!({
+                "type": "model",
+                "id": m.id,
+                "display_name": m.id,
+                "created_at": DateTime::from_timestamp(m.created, 0)
+                    .unwrap_or_default()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            }))
+            .collect::<Vec<_>>(),
+        "has_more": has_more,
+        "first_id": page.first().map(|m| &m.id),
+        "last_id": page.last().map(|m| &m.id),
+    })
+}
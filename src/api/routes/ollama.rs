@@ -0,0 +1,187 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use chrono::{DateTime, SecondsFormat};
+use std::time::Instant;
+use tracing::info;
+
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::routes::models::collect_models;
+use crate::api::routes::upstream;
+use crate::api::state::AppState;
+use crate::api::transformers::ollama_to_openai;
+use crate::api::transformers::openai_to_ollama::{self, Endpoint};
+use crate::core::metrics::RequestTracker;
+use crate::core::usage::{Usage, UsageScope};
+
+/// Ollama `/api/chat`.
+pub async fn chat(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Extension(tracker): Extension<RequestTracker>,
+    body: Bytes,
+) -> Response {
+    let body = match parse_body(&body) {
+        Ok(body) => body,
+        Err(rejection) => return rejection,
+    };
+    let openai_body = ollama_to_openai::transform_chat_request(&body);
+    forward(state, identity, tracker, &body, openai_body, Endpoint::Chat).await
+}
+
+/// Ollama `/api/generate`.
+pub async fn generate(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Extension(tracker): Extension<RequestTracker>,
+    body: Bytes,
+) -> Response {
+    let body = match parse_body(&body) {
+        Ok(body) => body,
+        Err(rejection) => return rejection,
+    };
+    let openai_body = ollama_to_openai::transform_generate_request(&body);
+    forward(
+        state,
+        identity,
+        tracker,
+        &body,
+        openai_body,
+        Endpoint::Generate,
+    )
+    .await
+}
+
+/// Ollama `/api/tags`: the same models as `/v1/models`, in Ollama's shape.
+pub async fn tags(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+) -> Json<serde_json::Value> {
+    let models = collect_models(&state, &identity);
+    Json(serde_json::json!({
+        "models": models
+            .iter()
+            .map(|m| serde_json::json!({
+                "name": m.id,
+                "model": m.id,
+                "modified_at": DateTime::from_timestamp(m.created, 0)
+                    .unwrap_or_default()
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                "size": 0,
+                "digest": "",
+                "details": {
+                    "format": "",
+                    "family": m.owned_by,
+                    "families": null,
+                    "parameter_size": "",
+                    "quantization_level": ""
+                }
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Parses a JSON body; Ollama clients frequently omit the `Content-Type` header.
+#[allow(clippy::result_large_err)]
+fn parse_body(body: &[u8]) -> Result<serde_json::Value, Response> {
+    serde_json::from_slice(body).map_err(|e| {
+        error_response(
+            ApiFormat::Ollama,
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!("invalid JSON body: {}", e),
+        )
+    })
+}
+
+/// Sends a translated request upstream and renders the reply for the Ollama endpoint.
+async fn forward(
+    state: AppState,
+    identity: ClientIdentity,
+    tracker: RequestTracker,
+    ollama_body: &serde_json::Value,
+    openai_body: serde_json::Value,
+    endpoint: Endpoint,
+) -> Response {
+    let started_at = Instant::now();
+    let model = ollama_body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let is_stream = ollama_to_openai::is_stream(ollama_body);
+    let route = state.router.resolve(&model);
+    if let Err(rejection) = state.admit(&identity, &model, &route, ApiFormat::Ollama) {
+        return rejection;
+    }
+
+    tracker.set_target(&route);
+    info!(
+        model = %model,
+        client = %identity.name,
+        stream = is_stream,
+        endpoint = ?endpoint,
+        provider = %route.primary().provider,
+        upstream_model = %route.primary().model,
+        "Ollama request"
+    );
+
+    let response = match state.client.chat_completion(&route, openai_body).await {
+        Ok(r) => r,
+        Err(e) => return upstream::request_failed(ApiFormat::Ollama, &tracker, &e),
+    };
+
+    let upstream = response.provider().to_string();
+    let status = response.status();
+    tracker.set_upstream(&upstream);
+    tracker.upstream_status(Some(status.as_u16()));
+    if !status.is_success() {
+        return upstream::error_reply(ApiFormat::Ollama, response).await;
+    }
+    let scope = UsageScope {
+        client: identity.name.clone(),
+        model: model.clone(),
+        route: route.label().to_string(),
+        provider: upstream.clone(),
+        upstream_model: response.model().to_string(),
+    };
+
+    info!(upstream = %upstream, "Ollama request served");
+
+    if is_stream {
+        // Streaming: transform OpenAI SSE → Ollama NDJSON
+        let transformer = openai_to_ollama::StreamTransformer::new(&model, endpoint);
+        let body = upstream::translate_stream(state, scope, tracker, response, transformer);
+        upstream::streaming("application/x-ndjson", &upstream, body)
+    } else {
+        tracker.first_byte();
+
+        // Non-streaming: transform response
+        let openai_response = match upstream::read_json(ApiFormat::Ollama, response).await {
+            Ok(v) => v,
+            Err(rejection) => return rejection,
+        };
+
+        let cost = openai_response
+            .get("usage")
+            .map(Usage::from_openai)
+            .and_then(|usage| {
+                tracker.record_tokens(&usage);
+                state.record_usage(&scope, usage)
+            });
+
+        let ollama_response = openai_to_ollama::transform_response(
+            &openai_response,
+            &model,
+            endpoint,
+            started_at.elapsed(),
+        );
+
+        upstream::completed(&state, &upstream, ollama_response.to_string(), cost)
+    }
+}
@@ -1,84 +1,105 @@
 use axum::{
-    body::Body,
-    extract::State,
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    extract::{Extension, State},
+    response::Response,
     Json,
 };
-use tracing::{error, info};
+use tracing::info;
 
-use crate::core::OpenAiClient;
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::ApiFormat;
+use crate::api::routes::upstream;
+use crate::api::state::AppState;
+use crate::core::metrics::RequestTracker;
+use crate::core::usage::{Usage, UsageScope};
 
 pub async fn chat_completions(
-    State(client): State<OpenAiClient>,
-    Json(body): Json<serde_json::Value>,
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Extension(tracker): Extension<RequestTracker>,
+    Json(mut body): Json<serde_json::Value>,
 ) -> Response {
     let model = body
         .get("model")
         .and_then(|m| m.as_str())
-        .unwrap_or("unknown");
+        .unwrap_or("unknown")
+        .to_string();
     let is_stream = body
         .get("stream")
         .and_then(|s| s.as_bool())
         .unwrap_or(false);
+
+    let route = state.router.resolve(&model);
+    if let Err(rejection) = state.admit(&identity, &model, &route, ApiFormat::OpenAi) {
+        return rejection;
+    }
+
+    tracker.set_target(&route);
     info!(
-        model = model,
+        model = %model,
+        client = %identity.name,
         stream = is_stream,
+        provider = %route.primary().provider,
+        upstream_model = %route.primary().model,
         "OpenAI chat completions request"
     );
 
-    let response = match client.chat_completion(body).await {
-        Ok(r) => r,
-        Err(e) => {
-            error!(error = %e, "OpenAI API request failed");
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(serde_json::json!({
-                    "error": {
-                        "message": format!("OpenAI API error: {}", e),
-                        "type": "proxy_error"
-                    }
-                })),
-            )
-                .into_response();
+    // Always ask for usage so tokens can be counted; the extra chunk is hidden again
+    // from clients that did not request it themselves
+    let client_wants_usage = body
+        .pointer("/stream_options/include_usage")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if is_stream && !client_wants_usage {
+        match body.get_mut("stream_options") {
+            Some(serde_json::Value::Object(options)) => {
+                options.insert("include_usage".to_string(), serde_json::json!(true));
+            }
+            _ => body["stream_options"] = serde_json::json!({ "include_usage": true }),
         }
+    }
+
+    let response = match state.client.chat_completion(&route, body).await {
+        Ok(r) => r,
+        Err(e) => return upstream::request_failed(ApiFormat::OpenAi, &tracker, &e),
     };
 
+    let upstream = response.provider().to_string();
     let status = response.status();
-
+    tracker.set_upstream(&upstream);
+    tracker.upstream_status(Some(status.as_u16()));
     if !status.is_success() {
-        let status_code = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
-        let body_text = response.text().await.unwrap_or_default();
-        error!(status = %status_code, body = %body_text, "OpenAI API returned error");
-        return (status_code, body_text).into_response();
+        return upstream::error_reply(ApiFormat::OpenAi, response).await;
     }
+    let scope = UsageScope {
+        client: identity.name.clone(),
+        model: model.clone(),
+        route: route.label().to_string(),
+        provider: upstream.clone(),
+        upstream_model: response.model().to_string(),
+    };
 
-    if is_stream {
-        // Stream SSE directly from OpenAI to client
-        let stream = OpenAiClient::stream_response(response);
-        let body = Body::from_stream(stream);
+    info!(upstream = %upstream, "OpenAI chat completions served");
 
-        Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "text/event-stream")
-            .header("Cache-Control", "no-cache")
-            .header("Connection", "keep-alive")
-            .body(body)
-            .unwrap()
+    if is_stream {
+        let body = upstream::forward_stream(state, scope, tracker, response, !client_wants_usage);
+        upstream::streaming("text/event-stream", &upstream, body)
     } else {
+        tracker.first_byte();
+
         // Return JSON response directly
-        let body_text = match response.text().await {
+        let body_text = match upstream::read_body(ApiFormat::OpenAi, response).await {
             Ok(t) => t,
-            Err(e) => {
-                error!(error = %e, "Failed to read OpenAI response");
-                return (StatusCode::BAD_GATEWAY, "Failed to read response").into_response();
-            }
+            Err(rejection) => return rejection,
         };
 
-        Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "application/json")
-            .body(Body::from(body_text))
-            .unwrap()
+        let cost = serde_json::from_str::<serde_json::Value>(&body_text)
+            .ok()
+            .and_then(|v| v.get("usage").map(Usage::from_openai))
+            .and_then(|usage| {
+                tracker.record_tokens(&usage);
+                state.record_usage(&scope, usage)
+            });
+
+        upstream::completed(&state, &upstream, body_text, cost)
     }
 }
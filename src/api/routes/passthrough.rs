@@ -0,0 +1,198 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Extension, FromRequest, Multipart, Request, State},
+    http::{header::CONTENT_TYPE, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tracing::info;
+
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::routes::upstream;
+use crate::api::state::AppState;
+use crate::core::client::RequestBody;
+use crate::core::config::ProviderKind;
+use crate::core::metrics::RequestTracker;
+use crate::core::usage::{Usage, UsageScope};
+
+/// Largest passthrough body accepted; audio uploads for transcription run up to 25 MB.
+pub const MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
+/// Forwards an OpenAI-style request (`/v1/completions`, `/v1/embeddings` or a configured
+/// passthrough path) unchanged to the routed provider, at the same path under its base URL.
+///
+/// Only `kind: openai` providers speak these endpoints, so other targets are dropped from
+/// the fallback chain.
+pub async fn forward(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Extension(tracker): Extension<RequestTracker>,
+    request: Request,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let bytes = match Bytes::from_request(Request::from_parts(parts.clone(), body), &()).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let path = parts.uri.path();
+    let upstream_path = path.strip_prefix("/v1").unwrap_or(path);
+    let (model, is_stream, body) = match inspect(&parts, bytes).await {
+        Ok(inspected) => inspected,
+        Err(rejection) => return rejection,
+    };
+    let model = model.unwrap_or_else(|| "unknown".to_string());
+
+    let mut route = state.router.resolve(&model);
+    if let Err(rejection) = state.admit(&identity, &model, &route, ApiFormat::OpenAi) {
+        return rejection;
+    }
+
+    route.targets.retain(|target| {
+        state
+            .client
+            .provider(&target.provider)
+            .is_some_and(|p| p.kind() == ProviderKind::OpenAi)
+    });
+    if route.targets.is_empty() {
+        return error_response(
+            ApiFormat::OpenAi,
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!(
+                "model '{}' is not routed to an OpenAI-compatible provider, which {} requires",
+                model, path
+            ),
+        );
+    }
+
+    tracker.set_target(&route);
+    info!(
+        model = %model,
+        client = %identity.name,
+        stream = is_stream,
+        path = %path,
+        provider = %route.primary().provider,
+        upstream_model = %route.primary().model,
+        "OpenAI passthrough request"
+    );
+
+    let response = match state.client.passthrough(&route, upstream_path, body).await {
+        Ok(r) => r,
+        Err(e) => return upstream::request_failed(ApiFormat::OpenAi, &tracker, &e),
+    };
+
+    let upstream = response.provider().to_string();
+    let status = response.status();
+    tracker.set_upstream(&upstream);
+    tracker.upstream_status(Some(status.as_u16()));
+    if !status.is_success() {
+        return upstream::error_reply(ApiFormat::OpenAi, response).await;
+    }
+    let scope = UsageScope {
+        client: identity.name.clone(),
+        model: model.clone(),
+        route: route.label().to_string(),
+        provider: upstream.clone(),
+        upstream_model: response.model().to_string(),
+    };
+
+    info!(upstream = %upstream, path = %path, "OpenAI passthrough served");
+
+    if is_stream {
+        // Stream SSE through untouched, booking usage if the upstream reports it
+        let body = upstream::forward_stream(state, scope, tracker, response, false);
+        upstream::streaming("text/event-stream", &upstream, body)
+    } else {
+        tracker.first_byte();
+
+        let body_text = match upstream::read_body(ApiFormat::OpenAi, response).await {
+            Ok(t) => t,
+            Err(rejection) => return rejection,
+        };
+
+        // Embeddings report prompt tokens only; that is still worth booking
+        let cost = serde_json::from_str::<serde_json::Value>(&body_text)
+            .ok()
+            .and_then(|v| v.get("usage").map(Usage::from_openai))
+            .and_then(|usage| {
+                tracker.record_tokens(&usage);
+                state.record_usage(&scope, usage)
+            });
+
+        upstream::completed(&state, &upstream, body_text, cost)
+    }
+}
+
+/// Reads the model and stream flag a request is routed by, and the body to send on.
+///
+/// JSON bodies (assumed when no `Content-Type` is given) carry them as fields and have the
+/// routed model swapped in per target. Multipart forms such as audio uploads carry them as
+/// form fields and, like any other content type, go upstream byte for byte.
+#[allow(clippy::result_large_err)]
+async fn inspect(
+    parts: &Parts,
+    bytes: Bytes,
+) -> Result<(Option<String>, bool, RequestBody), Response> {
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/json");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime == "application/json" || mime.ends_with("+json") {
+        let body: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| {
+            error_response(
+                ApiFormat::OpenAi,
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &format!("invalid JSON body: {}", e),
+            )
+        })?;
+        let model = body
+            .get("model")
+            .and_then(|m| m.as_str())
+            .map(str::to_string);
+        let is_stream = body
+            .get("stream")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false);
+        return Ok((model, is_stream, RequestBody::Json(body)));
+    }
+
+    let (model, is_stream) = if mime == "multipart/form-data" {
+        form_fields(parts, &bytes).await
+    } else {
+        (None, false)
+    };
+    let body = RequestBody::Raw {
+        content_type: content_type.to_string(),
+        bytes,
+    };
+    Ok((model, is_stream, body))
+}
+
+/// The `model` and `stream` fields of a multipart form; a malformed form yields neither and
+/// is left for the upstream to reject.
+async fn form_fields(parts: &Parts, bytes: &Bytes) -> (Option<String>, bool) {
+    let request = Request::from_parts(parts.clone(), Body::from(bytes.clone()));
+    let Ok(mut multipart) = Multipart::from_request(request, &()).await else {
+        return (None, false);
+    };
+
+    let mut model = None;
+    let mut is_stream = false;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name() {
+            Some("model") => model = field.text().await.ok(),
+            Some("stream") => is_stream = field.text().await.is_ok_and(|v| v.trim() == "true"),
+            _ => {}
+        }
+    }
+    (model, is_stream)
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use tracing::info;
+
+use crate::api::auth::ClientIdentity;
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::routes::upstream;
+use crate::api::state::AppState;
+use crate::api::transformers::{openai_to_responses, responses_to_openai};
+use crate::core::metrics::RequestTracker;
+use crate::core::usage::{Usage, UsageScope};
+
+pub async fn responses(
+    State(state): State<AppState>,
+    Extension(identity): Extension<ClientIdentity>,
+    Extension(tracker): Extension<RequestTracker>,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or("unknown");
+    let is_stream = body
+        .get("stream")
+        .and_then(|s| s.as_bool())
+        .unwrap_or(false);
+    let route = state.router.resolve(model);
+    if let Err(rejection) = state.admit(&identity, model, &route, ApiFormat::OpenAi) {
+        return rejection;
+    }
+
+    // Responses are not stored, so there is nothing to continue from
+    if body
+        .get("previous_response_id")
+        .is_some_and(|id| !id.is_null())
+    {
+        return error_response(
+            ApiFormat::OpenAi,
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "previous_response_id is not supported by this proxy; send the full input instead",
+        );
+    }
+
+    tracker.set_target(&route);
+    info!(
+        model = model,
+        client = %identity.name,
+        stream = is_stream,
+        provider = %route.primary().provider,
+        upstream_model = %route.primary().model,
+        "OpenAI responses request"
+    );
+
+    // Transform Responses request → chat completions format
+    let openai_body = responses_to_openai::transform_request(&body);
+
+    let response = match state.client.chat_completion(&route, openai_body).await {
+        Ok(r) => r,
+        Err(e) => return upstream::request_failed(ApiFormat::OpenAi, &tracker, &e),
+    };
+
+    let upstream = response.provider().to_string();
+    let status = response.status();
+    tracker.set_upstream(&upstream);
+    tracker.upstream_status(Some(status.as_u16()));
+    if !status.is_success() {
+        return upstream::error_reply(ApiFormat::OpenAi, response).await;
+    }
+    let scope = UsageScope {
+        client: identity.name.clone(),
+        model: model.to_string(),
+        route: route.label().to_string(),
+        provider: upstream.clone(),
+        upstream_model: response.model().to_string(),
+    };
+
+    info!(upstream = %upstream, "OpenAI responses served");
+
+    if is_stream {
+        // Streaming: transform chat completion chunks → Responses SSE events
+        let transformer = openai_to_responses::StreamTransformer::new(model, &body);
+        let body = upstream::translate_stream(state, scope, tracker, response, transformer);
+        upstream::streaming("text/event-stream", &upstream, body)
+    } else {
+        tracker.first_byte();
+
+        // Non-streaming: transform response
+        let openai_response = match upstream::read_json(ApiFormat::OpenAi, response).await {
+            Ok(v) => v,
+            Err(rejection) => return rejection,
+        };
+
+        let cost = openai_response
+            .get("usage")
+            .map(Usage::from_openai)
+            .and_then(|usage| {
+                tracker.record_tokens(&usage);
+                state.record_usage(&scope, usage)
+            });
+
+        let responses_response =
+            openai_to_responses::transform_response(&openai_response, model, &body);
+
+        upstream::completed(&state, &upstream, responses_response.to_string(), cost)
+    }
+}
@@ -0,0 +1,298 @@
+//! Relaying an upstream chat completion back to the client: error replies, the streaming
+//! task and the headers every completion route sets.
+
+use axum::{
+    body::Body,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use std::convert::Infallible;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
+
+use crate::api::errors::{error_response, ApiFormat};
+use crate::api::state::AppState;
+use crate::api::transformers::{openai_to_anthropic, openai_to_ollama, openai_to_responses};
+use crate::common::sse;
+use crate::core::client::{UpstreamResponse, UPSTREAM_HEADER};
+use crate::core::metrics::RequestTracker;
+use crate::core::pricing::COST_HEADER;
+use crate::core::usage::{Usage, UsageScope};
+
+/// Buffered events between the relay task and the client connection.
+const STREAM_BUFFER: usize = 128;
+
+/// 502 for a request no provider in the fallback chain answered.
+pub fn request_failed(
+    format: ApiFormat,
+    tracker: &RequestTracker,
+    error: &reqwest::Error,
+) -> Response {
+    error!(error = %error, "Upstream request failed");
+    tracker.upstream_status(None);
+    let error_type = match format {
+        ApiFormat::Anthropic => "api_error",
+        _ => "proxy_error",
+    };
+    error_response(
+        format,
+        StatusCode::BAD_GATEWAY,
+        error_type,
+        &format!("Upstream request failed: {}", error),
+    )
+}
+
+/// Relays a non-success upstream reply with its status. OpenAI clients get the body as is;
+/// other formats get its error message in their own error shape.
+pub async fn error_reply(format: ApiFormat, response: UpstreamResponse) -> Response {
+    let upstream = response.provider().to_string();
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let body_text = response.text().await.unwrap_or_default();
+    error!(status = %status, body = %body_text, upstream = %upstream, "Upstream returned error");
+
+    let mut reply = match format {
+        ApiFormat::OpenAi => (status, body_text).into_response(),
+        _ => {
+            let message = serde_json::from_str::<serde_json::Value>(&body_text)
+                .ok()
+                .and_then(|v| {
+                    v.pointer("/error/message")
+                        .and_then(|m| m.as_str())
+                        .map(str::to_string)
+                })
+                .unwrap_or(body_text);
+            error_response(format, status, "api_error", &message)
+        }
+    };
+    if let Ok(value) = upstream.parse() {
+        reply.headers_mut().insert(UPSTREAM_HEADER, value);
+    }
+    reply
+}
+
+/// Reads a successful reply body; a body that breaks off midway is a 502.
+#[allow(clippy::result_large_err)]
+pub async fn read_body(format: ApiFormat, response: UpstreamResponse) -> Result<String, Response> {
+    response.text().await.map_err(|e| {
+        error!(error = %e, "Failed to read upstream response");
+        error_response(
+            format,
+            StatusCode::BAD_GATEWAY,
+            "api_error",
+            "Failed to read upstream response",
+        )
+    })
+}
+
+/// Reads a successful reply for translation; a body that is not JSON is a 502.
+#[allow(clippy::result_large_err)]
+pub async fn read_json(
+    format: ApiFormat,
+    response: UpstreamResponse,
+) -> Result<serde_json::Value, Response> {
+    let body_text = read_body(format, response).await?;
+    serde_json::from_str(&body_text).map_err(|e| {
+        error!(error = %e, body = %body_text, "Failed to parse upstream response");
+        error_response(
+            format,
+            StatusCode::BAD_GATEWAY,
+            "api_error",
+            "Failed to parse upstream response",
+        )
+    })
+}
+
+/// A completed, non-streaming reply: `body` is JSON, tagged with the upstream that served it
+/// and, when `pricing.cost_header` is on, the request's cost.
+pub fn completed(
+    state: &AppState,
+    upstream: &str,
+    body: impl Into<Body>,
+    cost: Option<f64>,
+) -> Response {
+    let mut builder = Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .header(UPSTREAM_HEADER, upstream);
+    if let Some(cost) = cost.filter(|_| state.prices.cost_header()) {
+        builder = builder.header(COST_HEADER, format!("{:.6}", cost));
+    }
+    builder.body(body.into()).unwrap()
+}
+
+/// Headers for a streamed reply of `content_type`.
+pub fn streaming(content_type: &str, upstream: &str, body: Body) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .header("Cache-Control", "no-cache")
+        .header("Connection", "keep-alive")
+        .header(UPSTREAM_HEADER, upstream)
+        .body(body)
+        .unwrap()
+}
+
+/// Re-encodes upstream chat completion chunks for a client API, tallying usage on the way.
+pub trait ChunkTranslator: Send + 'static {
+    /// Events sent before the first upstream chunk arrives.
+    fn start(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+    fn process_chunk(&mut self, data: &str) -> Vec<String>;
+    /// Called once the upstream closes; terminates the client's stream.
+    fn finish(&mut self) -> Vec<String>;
+    fn usage(&self) -> Usage;
+}
+
+impl ChunkTranslator for openai_to_anthropic::StreamTransformer {
+    fn start(&mut self) -> Vec<String> {
+        vec![self.start_event()]
+    }
+
+    fn process_chunk(&mut self, data: &str) -> Vec<String> {
+        self.process_chunk(data)
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        self.finish()
+    }
+
+    fn usage(&self) -> Usage {
+        self.usage()
+    }
+}
+
+impl ChunkTranslator for openai_to_responses::StreamTransformer {
+    fn start(&mut self) -> Vec<String> {
+        self.start_events()
+    }
+
+    fn process_chunk(&mut self, data: &str) -> Vec<String> {
+        self.process_chunk(data)
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        self.finish()
+    }
+
+    fn usage(&self) -> Usage {
+        self.usage()
+    }
+}
+
+impl ChunkTranslator for openai_to_ollama::StreamTransformer {
+    fn process_chunk(&mut self, data: &str) -> Vec<String> {
+        self.process_chunk(data)
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        self.finish()
+    }
+
+    fn usage(&self) -> Usage {
+        self.usage()
+    }
+}
+
+/// Streams the upstream's chunks through `translator` from a background task, booking the
+/// usage once the stream ends, even if the client disconnected.
+pub fn translate_stream<T: ChunkTranslator>(
+    state: AppState,
+    scope: UsageScope,
+    tracker: RequestTracker,
+    response: UpstreamResponse,
+    mut translator: T,
+) -> Body {
+    let mut data_lines = sse::data_lines(response.into_stream());
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, Infallible>>(STREAM_BUFFER);
+
+    tokio::spawn(async move {
+        // Forward events until the upstream ends or the client goes away
+        let mut events = translator.start();
+        'forward: loop {
+            for event in events.drain(..) {
+                if tx.send(Ok(event)).await.is_err() {
+                    tracker.stream_aborted();
+                    break 'forward;
+                }
+            }
+            match data_lines.next().await {
+                Some(data) => {
+                    tracker.first_byte();
+                    events = translator.process_chunk(&data);
+                }
+                None => {
+                    // Always send final events to ensure proper stream termination
+                    for event in translator.finish() {
+                        if tx.send(Ok(event)).await.is_err() {
+                            tracker.stream_aborted();
+                            break;
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+
+        let usage = translator.usage();
+        tracker.record_tokens(&usage);
+        state.record_usage(&scope, usage);
+    });
+
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+/// Streams the upstream's SSE through line by line, byte for byte (comments and `event:`
+/// lines included), watching `data:` lines for the usage chunk on the way.
+///
+/// With `hide_usage`, usage-only events are dropped again, for clients that did not ask for
+/// them.
+pub fn forward_stream(
+    state: AppState,
+    scope: UsageScope,
+    tracker: RequestTracker,
+    response: UpstreamResponse,
+    hide_usage: bool,
+) -> Body {
+    let mut lines = sse::raw_lines(response.into_stream());
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, Infallible>>(STREAM_BUFFER);
+
+    tokio::spawn(async move {
+        let mut usage = Usage::default();
+        // Set after hiding a usage-only event, to drop the blank line that ends it
+        let mut skip_blank = false;
+        while let Some(line) = lines.next().await {
+            tracker.first_byte();
+            if std::mem::take(&mut skip_blank) && line.trim_ascii().is_empty() {
+                continue;
+            }
+            let mut forward = true;
+            if let Some(chunk) = sse::data_payload(&line)
+                .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+            {
+                if let Some(u) = chunk.get("usage").filter(|u| !u.is_null()) {
+                    usage = Usage::from_openai(u);
+                    let has_choices = chunk
+                        .get("choices")
+                        .and_then(|c| c.as_array())
+                        .is_some_and(|c| !c.is_empty());
+                    forward = !hide_usage || has_choices;
+                    skip_blank = !forward;
+                }
+            }
+            if forward && tx.send(Ok(Bytes::from(line))).await.is_err() {
+                tracker.stream_aborted();
+                break;
+            }
+        }
+
+        // Book whatever usage the upstream reported, even if the client disconnected
+        tracker.record_tokens(&usage);
+        state.record_usage(&scope, usage);
+    });
+
+    Body::from_stream(ReceiverStream::new(rx))
+}
@@ -0,0 +1,240 @@
+use axum::{http::StatusCode, response::Response};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::api::auth::{ClientIdentity, ClientKeys};
+use crate::api::errors::{error_response, rate_limited_response, ApiFormat};
+use crate::core::accounting::UsageLedger;
+use crate::core::batches::BatchStore;
+use crate::core::files::FileStore;
+use crate::core::health::HealthMonitor;
+use crate::core::limits::RateLimiter;
+use crate::core::message_batches::MessageBatchStore;
+use crate::core::metrics::Metrics;
+use crate::core::pricing::PriceTable;
+use crate::core::router::ResolvedRoute;
+use crate::core::signing::ThinkingSigner;
+use crate::core::tokenizer::Tokenizer;
+use crate::core::usage::{Usage, UsageScope};
+use crate::core::{ModelRouter, OpenAiClient};
+
+/// Shared state handed to every route handler.
+#[derive(Clone)]
+pub struct AppState {
+    pub client: OpenAiClient,
+    pub router: Arc<ModelRouter>,
+    pub client_keys: Arc<ClientKeys>,
+    pub limiter: Arc<RateLimiter>,
+    pub ledger: Arc<UsageLedger>,
+    pub prices: Arc<PriceTable>,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<HealthMonitor>,
+    pub tokenizer: Arc<dyn Tokenizer>,
+    pub message_batches: Arc<MessageBatchStore>,
+    pub batches: Arc<BatchStore>,
+    pub files: Arc<FileStore>,
+    pub signer: Arc<ThinkingSigner>,
+}
+
+impl AppState {
+    /// Checks that the client may use `model`, resolved to `route`, right now, rendering any
+    /// rejection in `format`.
+    #[allow(clippy::result_large_err)]
+    pub fn admit(
+        &self,
+        identity: &ClientIdentity,
+        model: &str,
+        route: &ResolvedRoute,
+        format: ApiFormat,
+    ) -> Result<(), Response> {
+        Self::check_model(identity, model, format)?;
+        Self::check_budget(&self.ledger, identity, format)?;
+        self.check_rate(identity, model, route, format)
+    }
+
+    /// Takes one request from the client's rate limits, rejecting it when they are exhausted.
+    #[allow(clippy::result_large_err)]
+    pub fn check_rate(
+        &self,
+        identity: &ClientIdentity,
+        model: &str,
+        route: &ResolvedRoute,
+        format: ApiFormat,
+    ) -> Result<(), Response> {
+        let Err(limited) = self.limiter.check(&identity.name, model, route.label()) else {
+            return Ok(());
+        };
+        warn!(
+            client = %identity.name,
+            model = %model,
+            retry_after_ms = limited.retry_after.as_millis() as u64,
+            "Client rate limited"
+        );
+        Err(rate_limited_response(format, &limited))
+    }
+
+    /// Checks only the key's model allow-list; with [`check_rate`](Self::check_rate), what
+    /// requests that cost nothing upstream go through, so they are kept out of budgets.
+    #[allow(clippy::result_large_err)]
+    pub fn check_model(
+        identity: &ClientIdentity,
+        model: &str,
+        format: ApiFormat,
+    ) -> Result<(), Response> {
+        if identity.allows_model(model) {
+            return Ok(());
+        }
+        warn!(client = %identity.name, model = %model, "Model not allowed for client key");
+        Err(error_response(
+            format,
+            StatusCode::FORBIDDEN,
+            "permission_error",
+            &format!(
+                "API key '{}' is not allowed to use model '{}'",
+                identity.name, model
+            ),
+        ))
+    }
+
+    /// Rejects clients that have used up a monthly budget: 402 in Anthropic's format, 429
+    /// `insufficient_quota` in OpenAI's.
+    #[allow(clippy::result_large_err)]
+    fn check_budget(
+        ledger: &UsageLedger,
+        identity: &ClientIdentity,
+        format: ApiFormat,
+    ) -> Result<(), Response> {
+        let Some(exhausted) = Self::exhausted_budget(ledger, identity) else {
+            return Ok(());
+        };
+        let status = match format {
+            ApiFormat::Anthropic => StatusCode::PAYMENT_REQUIRED,
+            ApiFormat::OpenAi | ApiFormat::Ollama => StatusCode::TOO_MANY_REQUESTS,
+        };
+        Err(error_response(
+            format,
+            status,
+            "billing_error",
+            &format!("{} exhausted for API key '{}'", exhausted, identity.name),
+        ))
+    }
+
+    /// Names the first monthly budget the client has used up, if any.
+    fn exhausted_budget(ledger: &UsageLedger, identity: &ClientIdentity) -> Option<String> {
+        if identity.monthly_token_budget.is_none() && identity.monthly_cost_budget.is_none() {
+            return None;
+        }
+        let used = ledger.month_totals(&identity.name);
+        if let Some(budget) = identity
+            .monthly_token_budget
+            .filter(|budget| used.tokens() >= *budget)
+        {
+            warn!(
+                client = %identity.name,
+                used = used.tokens(),
+                budget,
+                "Monthly token budget exhausted"
+            );
+            return Some(format!("Monthly token budget of {}", budget));
+        }
+        let budget = identity
+            .monthly_cost_budget
+            .filter(|budget| used.cost_usd >= *budget)?;
+        warn!(
+            client = %identity.name,
+            used_usd = used.cost_usd,
+            budget_usd = budget,
+            "Monthly cost budget exhausted"
+        );
+        Some(format!("Monthly cost budget of ${}", budget))
+    }
+
+    /// Books the tokens a finished request used against the client's limits and budget,
+    /// returning its estimated cost when the upstream model has a price.
+    pub fn record_usage(&self, scope: &UsageScope, usage: Usage) -> Option<f64> {
+        self.limiter
+            .record_tokens(&scope.client, &scope.model, &scope.route, usage.total());
+        if usage.total() == 0 {
+            return None;
+        }
+        let cost = self
+            .prices
+            .cost(&scope.provider, &scope.upstream_model, &usage);
+        self.ledger.record(scope, &usage, cost);
+        info!(
+            client = %scope.client,
+            model = %scope.model,
+            upstream = %scope.provider,
+            input_tokens = usage.input_tokens,
+            output_tokens = usage.output_tokens,
+            cached_tokens = usage.cached_tokens,
+            cost_usd = cost,
+            "Request usage"
+        );
+        cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::AuthConfig;
+
+    fn identity(budget: &str) -> ClientIdentity {
+        let config: AuthConfig =
+            serde_yaml::from_str(&format!("keys: [{{ name: a, key: k, {} }}]", budget)).unwrap();
+        ClientKeys::new(&config).identity_by_name("a").unwrap()
+    }
+
+    /// A ledger replayed from a log in which client `a` used 150 tokens costing $2.
+    fn ledger(name: &str) -> UsageLedger {
+        let path =
+            std::env::temp_dir().join(format!("budget-{}-{}.jsonl", name, std::process::id()));
+        let line = serde_json::json!({
+            "timestamp": "2026-01-01T00:00:00.000Z",
+            "day": chrono::Utc::now().date_naive(),
+            "client": "a",
+            "model": "m",
+            "route": "other",
+            "provider": "openai",
+            "upstream_model": "m",
+            "input_tokens": 100,
+            "output_tokens": 50,
+            "cost_usd": 2.0
+        });
+        std::fs::write(&path, line.to_string()).unwrap();
+        let ledger = UsageLedger::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        ledger
+    }
+
+    #[test]
+    fn spent_token_budget_is_402_for_anthropic_and_429_for_openai() {
+        let ledger = ledger("tokens");
+        let identity = identity("monthly_token_budget: 150");
+        let status = |format| {
+            AppState::check_budget(&ledger, &identity, format)
+                .unwrap_err()
+                .status()
+        };
+        assert_eq!(status(ApiFormat::Anthropic), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(status(ApiFormat::OpenAi), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn cost_budget_applies_once_spent() {
+        let ledger = ledger("cost");
+        let format = ApiFormat::Anthropic;
+        let spent = identity("monthly_cost_budget: 2.0");
+        assert_eq!(
+            AppState::check_budget(&ledger, &spent, format)
+                .unwrap_err()
+                .status(),
+            StatusCode::PAYMENT_REQUIRED
+        );
+        for budget in ["monthly_cost_budget: 2.5", "monthly_token_budget: 151", ""] {
+            let identity = identity(budget);
+            assert!(AppState::check_budget(&ledger, &identity, format).is_ok());
+        }
+    }
+}
@@ -1,9 +1,15 @@
 use crate::common::content_utils::extract_text_from_blocks;
+use crate::core::signing::ThinkingSigner;
+use crate::core::usage::Usage;
 use serde_json::{json, Value};
 use tracing::debug;
+use uuid::Uuid;
 
 /// Transforms an Anthropic Messages API request into an OpenAI-compatible request for the upstream API.
-pub fn transform_request(anthropic_body: &Value) -> Value {
+///
+/// Thinking blocks in history whose signature `signer` verifies become `reasoning_content`
+/// on their assistant message; the provider's profile decides whether that is sent upstream.
+pub fn transform_request(anthropic_body: &Value, signer: &ThinkingSigner) -> Value {
     let model = anthropic_body.get("model").cloned().unwrap_or(json!(""));
     let max_tokens = anthropic_body
         .get("max_tokens")
@@ -52,7 +58,7 @@ pub fn transform_request(anthropic_body: &Value) -> Value {
                     }));
                 }
                 Some(Value::Array(blocks)) => {
-                    convert_content_blocks(role, blocks, &mut openai_messages);
+                    convert_content_blocks(role, blocks, &mut openai_messages, signer);
                 }
                 _ => {}
             }
@@ -113,18 +119,10 @@ pub fn transform_request(anthropic_body: &Value) -> Value {
         }
     }
 
-    // Thinking / reasoning support
-    if let Some(thinking) = anthropic_body.get("thinking") {
-        if let Some(Value::Bool(true)) = thinking.get("enabled") {
-            // Some models use reasoning_effort or specific thinking params
-            if let Some(budget) = thinking.get("budget_tokens") {
-                openai_body["reasoning"] = json!({
-                    "max_tokens": budget
-                });
-            }
-            // Some models use thinking parameter directly
-            openai_body["thinking"] = thinking.clone();
-        }
+    // Extended thinking stays as Anthropic's `thinking` object; the client rewrites it into
+    // whatever field the provider's profile names (reasoning_effort, chat_template_kwargs, ...)
+    if let Some(thinking) = anthropic_body.get("thinking").and_then(normalize_thinking) {
+        openai_body["thinking"] = thinking;
     }
 
     // Stream options
@@ -136,45 +134,46 @@ pub fn transform_request(anthropic_body: &Value) -> Value {
     openai_body
 }
 
-fn convert_content_blocks(role: &str, blocks: &[Value], messages: &mut Vec<Value>) {
+/// Reduces a `thinking` request to `{"type": "enabled"|"adaptive"|"disabled"}` plus the
+/// budget; also accepts the older `{"enabled": true}` form.
+fn normalize_thinking(thinking: &Value) -> Option<Value> {
+    let kind = match thinking.get("type").and_then(|t| t.as_str()) {
+        Some(kind @ ("enabled" | "adaptive" | "disabled")) => kind,
+        Some(_) => return None,
+        None => match thinking.get("enabled").and_then(|e| e.as_bool()) {
+            Some(true) => "enabled",
+            Some(false) => "disabled",
+            None => return None,
+        },
+    };
+    let mut normalized = json!({ "type": kind });
+    if kind == "enabled" {
+        if let Some(budget) = thinking.get("budget_tokens").filter(|b| b.is_u64()) {
+            normalized["budget_tokens"] = budget.clone();
+        }
+    }
+    Some(normalized)
+}
+
+fn convert_content_blocks(
+    role: &str,
+    blocks: &[Value],
+    messages: &mut Vec<Value>,
+    signer: &ThinkingSigner,
+) {
     match role {
         "user" => {
             let mut parts: Vec<Value> = Vec::new();
             for block in blocks {
                 let block_type = block.get("type").and_then(|t| t.as_str()).unwrap_or("text");
                 match block_type {
-                    "text" => {
-                        parts.push(json!({
-                            "type": "text",
-                            "text": block.get("text").cloned().unwrap_or(json!(""))
-                        }));
-                    }
-                    "image" => {
-                        if let Some(source) = block.get("source") {
-                            let media_type = source
-                                .get("media_type")
-                                .and_then(|m| m.as_str())
-                                .unwrap_or("image/png");
-                            let data = source.get("data").and_then(|d| d.as_str()).unwrap_or("");
-                            parts.push(json!({
-                                "type": "image_url",
-                                "image_url": {
-                                    "url": format!("data:{};base64,{}", media_type, data)
-                                }
-                            }));
-                        }
-                    }
                     "tool_result" => {
                         // Tool results from user go as separate tool messages
                         let tool_use_id = block
                             .get("tool_use_id")
                             .and_then(|i| i.as_str())
                             .unwrap_or("");
-                        let content = match block.get("content") {
-                            Some(Value::String(s)) => s.clone(),
-                            Some(Value::Array(arr)) => extract_text_from_blocks(arr),
-                            _ => String::new(),
-                        };
+                        let content = tool_result_content(block.get("content"));
 
                         // Flush any accumulated parts first
                         if !parts.is_empty() {
@@ -185,13 +184,18 @@ fn convert_content_blocks(role: &str, blocks: &[Value], messages: &mut Vec<Value
                             parts = Vec::new();
                         }
 
-                        messages.push(json!({
+                        let mut tool_msg = json!({
                             "role": "tool",
                             "tool_call_id": tool_use_id,
                             "content": content
-                        }));
+                        });
+                        // Not OpenAI; the client turns it into a prefix or the upstream's own flag
+                        if block.get("is_error").and_then(|e| e.as_bool()) == Some(true) {
+                            tool_msg["is_error"] = json!(true);
+                        }
+                        messages.push(tool_msg);
                     }
-                    _ => {}
+                    _ => parts.extend(content_part(block)),
                 }
             }
             if !parts.is_empty() {
@@ -203,6 +207,8 @@ fn convert_content_blocks(role: &str, blocks: &[Value], messages: &mut Vec<Value
         }
         "assistant" => {
             let mut text_content = String::new();
+            let mut reasoning = String::new();
+            let mut upstream_signatures = Vec::new();
             let mut tool_calls: Vec<Value> = Vec::new();
 
             for block in blocks {
@@ -227,8 +233,17 @@ fn convert_content_blocks(role: &str, blocks: &[Value], messages: &mut Vec<Value
                         }));
                     }
                     "thinking" => {
-                        // Skip thinking blocks in history to prevent the model from mimicking the format
-                        // OpenAI-compatible APIs generally don't support input reasoning blocks.
+                        // Only the proxy's own blocks are replayed; anything else (other
+                        // proxies, edited history, redacted thinking) is dropped
+                        let thinking = block.get("thinking").and_then(|t| t.as_str());
+                        let signature = block.get("signature").and_then(|s| s.as_str());
+                        if let (Some(thinking), Some(signature)) = (thinking, signature) {
+                            if signer.verify(thinking, signature) {
+                                reasoning.push_str(thinking);
+                                upstream_signatures
+                                    .push(ThinkingSigner::upstream_signature(signature));
+                            }
+                        }
                     }
                     _ => {}
                 }
@@ -241,6 +256,13 @@ fn convert_content_blocks(role: &str, blocks: &[Value], messages: &mut Vec<Value
             if !tool_calls.is_empty() {
                 assistant_msg["tool_calls"] = json!(tool_calls);
             }
+            if !reasoning.is_empty() {
+                assistant_msg["reasoning_content"] = json!(reasoning);
+            }
+            // An upstream signature only holds for its own block, not for several joined
+            if let [Some(signature)] = upstream_signatures[..] {
+                assistant_msg["reasoning_signature"] = json!(signature);
+            }
             messages.push(assistant_msg);
         }
         _ => {
@@ -261,3 +283,505 @@ fn convert_content_blocks(role: &str, blocks: &[Value], messages: &mut Vec<Value
         }
     }
 }
+
+/// URL of the first `document` block, in a message or a tool result, that only gives its
+/// PDF by URL. The proxy doesn't fetch URLs for clients, so such requests are rejected rather
+/// than sent without the document.
+pub fn url_document(anthropic_body: &Value) -> Option<&str> {
+    fn find(blocks: &[Value]) -> Option<&str> {
+        blocks
+            .iter()
+            .find_map(|block| match block.get("type").and_then(|t| t.as_str()) {
+                Some("document") => {
+                    let source = block.get("source")?;
+                    if source.get("type").and_then(|t| t.as_str()) == Some("url") {
+                        source.get("url")?.as_str()
+                    } else {
+                        None
+                    }
+                }
+                Some("tool_result") => find(block.get("content")?.as_array()?),
+                _ => None,
+            })
+    }
+
+    anthropic_body
+        .get("messages")?
+        .as_array()?
+        .iter()
+        .find_map(|message| find(message.get("content")?.as_array()?))
+}
+
+/// OpenAI content part for an Anthropic user content block; unknown blocks are dropped.
+///
+/// PDFs become `file` parts, which the client may swap for extracted text per provider.
+fn content_part(block: &Value) -> Option<Value> {
+    match block.get("type").and_then(|t| t.as_str()).unwrap_or("text") {
+        "text" => Some(text_part(
+            block.get("text").and_then(|t| t.as_str()).unwrap_or(""),
+        )),
+        "image" => image_part(block.get("source")?),
+        "document" => document_part(block),
+        "search_result" => {
+            let mut text = String::new();
+            if let Some(title) = block.get("title").and_then(|t| t.as_str()) {
+                text.push_str(&format!("Search result: {}\n", title));
+            }
+            if let Some(source) = block.get("source").and_then(|s| s.as_str()) {
+                text.push_str(&format!("Source: {}\n", source));
+            }
+            if let Some(Value::Array(content)) = block.get("content") {
+                text.push('\n');
+                text.push_str(&extract_text_from_blocks(content));
+            }
+            Some(text_part(&text))
+        }
+        _ => None,
+    }
+}
+
+fn text_part(text: &str) -> Value {
+    json!({ "type": "text", "text": text })
+}
+
+/// OpenAI `image_url` part for an image `source`, either inline base64 or a URL.
+fn image_part(source: &Value) -> Option<Value> {
+    let url = match source.get("type").and_then(|t| t.as_str()) {
+        Some("url") => source.get("url").and_then(|u| u.as_str())?.to_string(),
+        _ => {
+            let media_type = source
+                .get("media_type")
+                .and_then(|m| m.as_str())
+                .unwrap_or("image/png");
+            let data = source.get("data").and_then(|d| d.as_str()).unwrap_or("");
+            format!("data:{};base64,{}", media_type, data)
+        }
+    };
+    Some(json!({
+        "type": "image_url",
+        "image_url": { "url": url }
+    }))
+}
+
+/// A `document` block: text documents are inlined with their title and context, base64
+/// PDFs become `file` parts, and PDF URLs are referenced in text.
+fn document_part(block: &Value) -> Option<Value> {
+    let source = block.get("source")?;
+    let title = block.get("title").and_then(|t| t.as_str());
+    let body = match source.get("type").and_then(|t| t.as_str()) {
+        Some("text") => source
+            .get("data")
+            .and_then(|d| d.as_str())
+            .unwrap_or("")
+            .to_string(),
+        Some("content") => match source.get("content") {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Array(blocks)) => extract_text_from_blocks(blocks),
+            _ => String::new(),
+        },
+        Some("base64") => {
+            let media_type = source
+                .get("media_type")
+                .and_then(|m| m.as_str())
+                .unwrap_or("application/pdf");
+            let data = source.get("data").and_then(|d| d.as_str()).unwrap_or("");
+            return Some(json!({
+                "type": "file",
+                "file": {
+                    "filename": title.unwrap_or("document.pdf"),
+                    "file_data": format!("data:{};base64,{}", media_type, data)
+                }
+            }));
+        }
+        // `url` sources are rejected by the route (see `url_document`)
+        _ => return None,
+    };
+
+    let mut text = String::new();
+    if let Some(title) = title {
+        text.push_str(&format!("Document: {}\n", title));
+    }
+    if let Some(context) = block.get("context").and_then(|c| c.as_str()) {
+        text.push_str(&format!("Context: {}\n", context));
+    }
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text.push_str(&body);
+    Some(text_part(&text))
+}
+
+/// Tool message content for a `tool_result`: plain text, or content parts when the tool
+/// returned images or PDFs (the client decides where those end up per provider).
+fn tool_result_content(content: Option<&Value>) -> Value {
+    match content {
+        Some(Value::String(s)) => json!(s),
+        Some(Value::Array(blocks)) => {
+            let parts: Vec<Value> = blocks.iter().filter_map(content_part).collect();
+            if parts
+                .iter()
+                .all(|p| p.get("type").and_then(|t| t.as_str()) == Some("text"))
+            {
+                let texts: Vec<&str> = parts
+                    .iter()
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .collect();
+                json!(texts.join("\n"))
+            } else {
+                json!(parts)
+            }
+        }
+        _ => json!(""),
+    }
+}
+
+/// Map Anthropic stop_reason to OpenAI finish_reason
+fn map_finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "tool_use" => "tool_calls",
+        "max_tokens" => "length",
+        "refusal" => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// OpenAI usage object for Anthropic's counters; Anthropic reports cached input separately.
+fn openai_usage(usage: &Usage) -> Value {
+    json!({
+        "prompt_tokens": usage.input_tokens,
+        "completion_tokens": usage.output_tokens,
+        "total_tokens": usage.total(),
+        "prompt_tokens_details": { "cached_tokens": usage.cached_tokens }
+    })
+}
+
+fn usage_from_anthropic(usage: &Value) -> Usage {
+    let count = |key: &str| usage.get(key).and_then(|t| t.as_u64()).unwrap_or(0);
+    let cached_tokens = count("cache_read_input_tokens");
+    Usage {
+        input_tokens: count("input_tokens") + cached_tokens + count("cache_creation_input_tokens"),
+        output_tokens: count("output_tokens"),
+        cached_tokens,
+    }
+}
+
+/// Transforms a non-streaming Anthropic Messages response into an OpenAI chat completion,
+/// for providers declared with `kind: anthropic`.
+///
+/// With `keep_signature`, the thinking signature is added as `reasoning_signature`; only a
+/// route that converts the completion back to Anthropic format has a use for it.
+pub fn transform_response(anthropic_response: &Value, keep_signature: bool) -> Value {
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut signatures = Vec::new();
+    let mut tool_calls: Vec<Value> = Vec::new();
+
+    if let Some(Value::Array(blocks)) = anthropic_response.get("content") {
+        for block in blocks {
+            match block.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+                "text" => {
+                    if let Some(t) = block.get("text").and_then(|t| t.as_str()) {
+                        text.push_str(t);
+                    }
+                }
+                "thinking" => {
+                    if let Some(t) = block.get("thinking").and_then(|t| t.as_str()) {
+                        reasoning.push_str(t);
+                    }
+                    signatures.push(block.get("signature").and_then(|s| s.as_str()));
+                }
+                "tool_use" => {
+                    let input = block.get("input").cloned().unwrap_or(json!({}));
+                    tool_calls.push(json!({
+                        "id": block.get("id").cloned().unwrap_or(json!("")),
+                        "type": "function",
+                        "function": {
+                            "name": block.get("name").cloned().unwrap_or(json!("")),
+                            "arguments": serde_json::to_string(&input).unwrap_or_default()
+                        }
+                    }));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut message = json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !tool_calls.is_empty() { Value::Null } else { json!(text) },
+    });
+    if !reasoning.is_empty() {
+        message["reasoning_content"] = json!(reasoning);
+    }
+    // Kept so the thinking can be replayed to Anthropic; see `openai_to_anthropic`
+    if keep_signature {
+        if let [Some(signature)] = signatures[..] {
+            message["reasoning_signature"] = json!(signature);
+        }
+    }
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = json!(tool_calls);
+    }
+
+    let finish_reason = anthropic_response
+        .get("stop_reason")
+        .and_then(|r| r.as_str())
+        .map(map_finish_reason)
+        .unwrap_or("stop");
+    let usage = usage_from_anthropic(anthropic_response.get("usage").unwrap_or(&json!({})));
+
+    json!({
+        "id": completion_id(anthropic_response.get("id")),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": anthropic_response.get("model").cloned().unwrap_or(json!("")),
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": finish_reason
+        }],
+        "usage": openai_usage(&usage)
+    })
+}
+
+/// Rewrites an Anthropic error body into OpenAI's error shape; other bodies are returned as-is.
+pub fn transform_error(body: &str) -> String {
+    let Ok(parsed) = serde_json::from_str::<Value>(body) else {
+        return body.to_string();
+    };
+    let Some(error) = parsed
+        .get("error")
+        .filter(|_| parsed.get("type") == Some(&json!("error")))
+    else {
+        return body.to_string();
+    };
+    json!({
+        "error": {
+            "message": error.get("message").cloned().unwrap_or(json!("")),
+            "type": error.get("type").cloned().unwrap_or(json!("api_error")),
+            "param": null,
+            "code": null
+        }
+    })
+    .to_string()
+}
+
+fn completion_id(message_id: Option<&Value>) -> String {
+    match message_id.and_then(|id| id.as_str()) {
+        Some(id) => format!("chatcmpl-{}", id.trim_start_matches("msg_")),
+        None => format!("chatcmpl-{}", Uuid::new_v4().simple()),
+    }
+}
+
+/// State machine for transforming streaming Anthropic SSE events into OpenAI chunks.
+///
+/// The mirror of `openai_to_anthropic::StreamTransformer`: each output string is a complete
+/// `data:` line, ending with a usage chunk and `[DONE]` on `message_stop`.
+pub struct StreamTransformer {
+    id: String,
+    model: String,
+    created: i64,
+    usage: Usage,
+    /// Content block index → OpenAI tool call index, for blocks that are tool uses
+    tool_blocks: Vec<(u64, usize)>,
+    /// Whether signature deltas are passed on as `reasoning_signature`, see `transform_response`
+    keep_signatures: bool,
+    finished: bool,
+}
+
+impl StreamTransformer {
+    pub fn new(model: &str, keep_signatures: bool) -> Self {
+        Self {
+            id: completion_id(None),
+            model: model.to_string(),
+            created: chrono::Utc::now().timestamp(),
+            usage: Usage::default(),
+            tool_blocks: Vec::new(),
+            keep_signatures,
+            finished: false,
+        }
+    }
+
+    /// Process the data of a single Anthropic SSE event and return OpenAI SSE lines
+    pub fn process_event(&mut self, data: &str) -> Vec<String> {
+        let event: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+
+        match event.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+            "message_start" => {
+                let message = event.get("message").cloned().unwrap_or(json!({}));
+                self.id = completion_id(message.get("id"));
+                if let Some(model) = message.get("model").and_then(|m| m.as_str()) {
+                    self.model = model.to_string();
+                }
+                self.usage = usage_from_anthropic(message.get("usage").unwrap_or(&json!({})));
+                vec![self.chunk(json!({ "role": "assistant", "content": "" }), None)]
+            }
+            "content_block_start" => {
+                let block = event.get("content_block").cloned().unwrap_or(json!({}));
+                if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                    return Vec::new();
+                }
+                let block_index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let tool_index = self.tool_blocks.len();
+                self.tool_blocks.push((block_index, tool_index));
+                vec![self.chunk(
+                    json!({
+                        "tool_calls": [{
+                            "index": tool_index,
+                            "id": block.get("id").cloned().unwrap_or(json!("")),
+                            "type": "function",
+                            "function": {
+                                "name": block.get("name").cloned().unwrap_or(json!("")),
+                                "arguments": ""
+                            }
+                        }]
+                    }),
+                    None,
+                )]
+            }
+            "content_block_delta" => {
+                let delta = event.get("delta").cloned().unwrap_or(json!({}));
+                let block_index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                match delta.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+                    "text_delta" => {
+                        let text = delta.get("text").cloned().unwrap_or(json!(""));
+                        vec![self.chunk(json!({ "content": text }), None)]
+                    }
+                    "thinking_delta" => {
+                        let thinking = delta.get("thinking").cloned().unwrap_or(json!(""));
+                        vec![self.chunk(json!({ "reasoning_content": thinking }), None)]
+                    }
+                    "signature_delta" if self.keep_signatures => {
+                        let signature = delta.get("signature").cloned().unwrap_or(json!(""));
+                        vec![self.chunk(json!({ "reasoning_signature": signature }), None)]
+                    }
+                    "input_json_delta" => {
+                        let Some(&(_, tool_index)) =
+                            self.tool_blocks.iter().find(|(b, _)| *b == block_index)
+                        else {
+                            return Vec::new();
+                        };
+                        let partial = delta.get("partial_json").cloned().unwrap_or(json!(""));
+                        vec![self.chunk(
+                            json!({
+                                "tool_calls": [{
+                                    "index": tool_index,
+                                    "function": { "arguments": partial }
+                                }]
+                            }),
+                            None,
+                        )]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            "message_delta" => {
+                if let Some(output) = event
+                    .pointer("/usage/output_tokens")
+                    .and_then(|t| t.as_u64())
+                {
+                    self.usage.output_tokens = output;
+                }
+                match event.pointer("/delta/stop_reason").and_then(|r| r.as_str()) {
+                    Some(reason) => vec![self.chunk(json!({}), Some(map_finish_reason(reason)))],
+                    None => Vec::new(),
+                }
+            }
+            "message_stop" => self.finish(),
+            "error" => {
+                self.finished = true;
+                let error = event.get("error").cloned().unwrap_or(json!({}));
+                vec![format_data(&json!({
+                    "error": {
+                        "message": error.get("message").cloned().unwrap_or(json!("")),
+                        "type": error.get("type").cloned().unwrap_or(json!("api_error")),
+                        "param": null,
+                        "code": null
+                    }
+                }))]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sends the usage chunk and `[DONE]`, once
+    pub fn finish(&mut self) -> Vec<String> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+        vec![
+            format_data(&json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [],
+                "usage": openai_usage(&self.usage)
+            })),
+            "data: [DONE]\n\n".to_string(),
+        ]
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> String {
+        format_data(&json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        }))
+    }
+}
+
+fn format_data(data: &Value) -> String {
+    format!(
+        "data: {}\n\n",
+        serde_json::to_string(data).unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thinking_response() -> Value {
+        json!({
+            "id": "msg_1",
+            "content": [
+                { "type": "thinking", "thinking": "hmm", "signature": "sig" },
+                { "type": "text", "text": "hi" }
+            ],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 3, "output_tokens": 2 }
+        })
+    }
+
+    #[test]
+    fn signatures_are_only_kept_when_asked_for() {
+        let plain = transform_response(&thinking_response(), false);
+        let message = &plain["choices"][0]["message"];
+        assert_eq!(message["reasoning_content"], "hmm");
+        assert!(message.get("reasoning_signature").is_none());
+
+        let kept = transform_response(&thinking_response(), true);
+        assert_eq!(kept["choices"][0]["message"]["reasoning_signature"], "sig");
+    }
+
+    #[test]
+    fn streamed_signatures_are_only_kept_when_asked_for() {
+        let delta = r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#;
+        assert!(StreamTransformer::new("m", false)
+            .process_event(delta)
+            .is_empty());
+        let kept = StreamTransformer::new("m", true).process_event(delta);
+        assert!(kept[0].contains(r#""reasoning_signature":"sig""#));
+    }
+}
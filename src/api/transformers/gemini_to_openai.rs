@@ -0,0 +1,375 @@
+use crate::common::tool_ids::encode_tool_call_id;
+use crate::core::usage::Usage;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Map Gemini finishReason to OpenAI finish_reason
+fn map_finish_reason(finish_reason: &str, has_tool_calls: bool) -> &'static str {
+    match finish_reason {
+        _ if has_tool_calls => "tool_calls",
+        "MAX_TOKENS" => "length",
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY" => {
+            "content_filter"
+        }
+        _ => "stop",
+    }
+}
+
+/// Gemini counts thinking tokens separately from the candidates; both are output.
+fn usage_from_gemini(metadata: &Value) -> Usage {
+    let count = |key: &str| metadata.get(key).and_then(|t| t.as_u64()).unwrap_or(0);
+    Usage {
+        input_tokens: count("promptTokenCount"),
+        output_tokens: count("candidatesTokenCount") + count("thoughtsTokenCount"),
+        cached_tokens: count("cachedContentTokenCount"),
+    }
+}
+
+fn openai_usage(usage: &Usage) -> Value {
+    json!({
+        "prompt_tokens": usage.input_tokens,
+        "completion_tokens": usage.output_tokens,
+        "total_tokens": usage.total(),
+        "prompt_tokens_details": { "cached_tokens": usage.cached_tokens }
+    })
+}
+
+/// OpenAI id for a `functionCall` part, carrying the part's `thoughtSignature` if it has one.
+fn tool_call_id(part: &Value) -> String {
+    let id = part
+        .pointer("/functionCall/id")
+        .and_then(|id| id.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("call_{}", Uuid::new_v4().simple()));
+    encode_tool_call_id(&id, part.get("thoughtSignature").and_then(|s| s.as_str()))
+}
+
+fn tool_call_arguments(part: &Value) -> String {
+    let args = part
+        .pointer("/functionCall/args")
+        .cloned()
+        .unwrap_or(json!({}));
+    serde_json::to_string(&args).unwrap_or_default()
+}
+
+/// Transforms a non-streaming Gemini `generateContent` response into an OpenAI chat completion,
+/// for providers declared with `kind: gemini`.
+pub fn transform_response(gemini_response: &Value, model: &str) -> Value {
+    let choices: Vec<Value> = gemini_response
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .map(|candidates| {
+            candidates
+                .iter()
+                .enumerate()
+                .map(|(i, candidate)| convert_candidate(i, candidate))
+                .collect()
+        })
+        .unwrap_or_default();
+    let usage = usage_from_gemini(gemini_response.get("usageMetadata").unwrap_or(&json!({})));
+
+    json!({
+        "id": gemini_response
+            .get("responseId")
+            .and_then(|id| id.as_str())
+            .map(|id| format!("chatcmpl-{}", id))
+            .unwrap_or_else(|| format!("chatcmpl-{}", Uuid::new_v4().simple())),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": gemini_response.get("modelVersion").and_then(|m| m.as_str()).unwrap_or(model),
+        "choices": choices,
+        "usage": openai_usage(&usage)
+    })
+}
+
+fn convert_candidate(index: usize, candidate: &Value) -> Value {
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls: Vec<Value> = Vec::new();
+
+    if let Some(Value::Array(parts)) = candidate.pointer("/content/parts") {
+        for part in parts {
+            if part.get("functionCall").is_some() {
+                tool_calls.push(json!({
+                    "id": tool_call_id(part),
+                    "type": "function",
+                    "function": {
+                        "name": part.pointer("/functionCall/name").cloned().unwrap_or(json!("")),
+                        "arguments": tool_call_arguments(part)
+                    }
+                }));
+            } else if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+                    reasoning.push_str(t);
+                } else {
+                    text.push_str(t);
+                }
+            }
+        }
+    }
+
+    let mut message = json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !tool_calls.is_empty() { Value::Null } else { json!(text) },
+    });
+    if !reasoning.is_empty() {
+        message["reasoning_content"] = json!(reasoning);
+    }
+    let has_tool_calls = !tool_calls.is_empty();
+    if has_tool_calls {
+        message["tool_calls"] = json!(tool_calls);
+    }
+
+    json!({
+        "index": index,
+        "message": message,
+        "finish_reason": candidate
+            .get("finishReason")
+            .and_then(|r| r.as_str())
+            .map(|r| map_finish_reason(r, has_tool_calls))
+            .unwrap_or("stop")
+    })
+}
+
+/// Rewrites a Gemini error body into OpenAI's error shape; other bodies are returned as-is.
+pub fn transform_error(body: &str) -> String {
+    let Some(error) = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|parsed| parsed.get("error").cloned())
+    else {
+        return body.to_string();
+    };
+    openai_error(&error).to_string()
+}
+
+fn openai_error(error: &Value) -> Value {
+    json!({
+        "error": {
+            "message": error.get("message").cloned().unwrap_or(json!("")),
+            "type": error
+                .get("status")
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_ascii_lowercase())
+                .unwrap_or_else(|| "api_error".to_string()),
+            "param": null,
+            "code": error.get("code").cloned().unwrap_or(Value::Null)
+        }
+    })
+}
+
+/// State machine for transforming `streamGenerateContent` SSE events into OpenAI chunks.
+///
+/// Gemini sends whole function calls in a single event and has no end marker, so the usage
+/// chunk and `[DONE]` are sent from `finish` once the upstream closes.
+pub struct StreamTransformer {
+    id: String,
+    model: String,
+    created: i64,
+    usage: Usage,
+    started: bool,
+    /// Tool calls emitted so far, per candidate index
+    tool_calls: Vec<usize>,
+    finished: bool,
+}
+
+impl StreamTransformer {
+    pub fn new(model: &str) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", Uuid::new_v4().simple()),
+            model: model.to_string(),
+            created: chrono::Utc::now().timestamp(),
+            usage: Usage::default(),
+            started: false,
+            tool_calls: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Process the data of a single Gemini SSE event and return OpenAI SSE lines
+    pub fn process_event(&mut self, data: &str) -> Vec<String> {
+        let mut events = Vec::new();
+        let response: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(_) => return events,
+        };
+
+        if let Some(error) = response.get("error") {
+            self.finished = true;
+            events.push(format_data(&openai_error(error)));
+            return events;
+        }
+
+        // Usage metadata is cumulative; the last one wins
+        if let Some(metadata) = response.get("usageMetadata") {
+            self.usage = usage_from_gemini(metadata);
+        }
+        if let Some(model) = response.get("modelVersion").and_then(|m| m.as_str()) {
+            self.model = model.to_string();
+        }
+
+        let Some(candidates) = response.get("candidates").and_then(|c| c.as_array()) else {
+            return events;
+        };
+
+        for (position, candidate) in candidates.iter().enumerate() {
+            let index = candidate
+                .get("index")
+                .and_then(|i| i.as_u64())
+                .map_or(position, |i| i as usize);
+            if self.tool_calls.len() <= index {
+                self.tool_calls.resize(index + 1, 0);
+            }
+            if !self.started {
+                self.started = true;
+                events.push(self.chunk(index, json!({ "role": "assistant", "content": "" }), None));
+            }
+
+            if let Some(Value::Array(parts)) = candidate.pointer("/content/parts") {
+                for part in parts {
+                    if part.get("functionCall").is_some() {
+                        let tool_index = self.tool_calls[index];
+                        self.tool_calls[index] += 1;
+                        let delta = json!({
+                            "tool_calls": [{
+                                "index": tool_index,
+                                "id": tool_call_id(part),
+                                "type": "function",
+                                "function": {
+                                    "name": part.pointer("/functionCall/name").cloned().unwrap_or(json!("")),
+                                    "arguments": tool_call_arguments(part)
+                                }
+                            }]
+                        });
+                        events.push(self.chunk(index, delta, None));
+                    } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                        if text.is_empty() {
+                            continue;
+                        }
+                        let delta = if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+                            json!({ "reasoning_content": text })
+                        } else {
+                            json!({ "content": text })
+                        };
+                        events.push(self.chunk(index, delta, None));
+                    }
+                }
+            }
+
+            if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
+                let finish_reason = map_finish_reason(reason, self.tool_calls[index] > 0);
+                events.push(self.chunk(index, json!({}), Some(finish_reason)));
+            }
+        }
+
+        events
+    }
+
+    /// Sends the usage chunk and `[DONE]`, once
+    pub fn finish(&mut self) -> Vec<String> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+        vec![
+            format_data(&json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [],
+                "usage": openai_usage(&self.usage)
+            })),
+            "data: [DONE]\n\n".to_string(),
+        ]
+    }
+
+    fn chunk(&self, index: usize, delta: Value, finish_reason: Option<&str>) -> String {
+        format_data(&json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": index,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        }))
+    }
+}
+
+fn format_data(data: &Value) -> String {
+    format!(
+        "data: {}\n\n",
+        serde_json::to_string(data).unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transformers::openai_to_gemini;
+    use crate::common::tool_ids::split_tool_call_id;
+
+    #[test]
+    fn thought_signatures_ride_in_tool_call_ids() {
+        let id = tool_call_id(&json!({
+            "functionCall": { "id": "call_1", "name": "f", "args": {} },
+            "thoughtSignature": "CiQB+/a9Zz=="
+        }));
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+        assert_eq!(
+            split_tool_call_id(&id),
+            ("call_1", Some("CiQB+/a9Zz==".to_string()))
+        );
+        assert_eq!(split_tool_call_id("call_2"), ("call_2", None));
+    }
+
+    #[test]
+    fn tool_calls_round_trip_with_their_thought_signature() {
+        let response = transform_response(
+            &json!({
+                "candidates": [{
+                    "content": { "role": "model", "parts": [
+                        { "text": "planning", "thought": true },
+                        { "functionCall": { "name": "get_weather", "args": { "city": "Paris" } },
+                          "thoughtSignature": "CiQB+/a9Zz==" }
+                    ] },
+                    "finishReason": "STOP"
+                }]
+            }),
+            "gemini",
+        );
+        let message = &response["choices"][0]["message"];
+        assert_eq!(response["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(message["reasoning_content"], "planning");
+        let id = message["tool_calls"][0]["id"].as_str().unwrap();
+
+        let request = openai_to_gemini::transform_request(&json!({
+            "model": "gemini",
+            "reasoning_effort": "low",
+            "messages": [
+                { "role": "user", "content": "weather?" },
+                message,
+                { "role": "tool", "tool_call_id": id, "content": "{\"temp\":20}" }
+            ]
+        }));
+        assert_eq!(
+            request["contents"][1],
+            json!({ "role": "model", "parts": [{
+                "functionCall": { "name": "get_weather", "args": { "city": "Paris" } },
+                "thoughtSignature": "CiQB+/a9Zz=="
+            }] })
+        );
+        assert_eq!(
+            request["contents"][2]["parts"][0]["functionResponse"],
+            json!({ "name": "get_weather", "response": { "temp": 20 } })
+        );
+        assert_eq!(
+            request["generationConfig"]["thinkingConfig"],
+            json!({ "thinkingBudget": 4096, "includeThoughts": true })
+        );
+    }
+}
@@ -1,2 +1,8 @@
 pub mod anthropic_to_openai;
+pub mod gemini_to_openai;
+pub mod ollama_to_openai;
 pub mod openai_to_anthropic;
+pub mod openai_to_gemini;
+pub mod openai_to_ollama;
+pub mod openai_to_responses;
+pub mod responses_to_openai;
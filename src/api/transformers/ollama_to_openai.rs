@@ -0,0 +1,202 @@
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use tracing::debug;
+
+/// Transforms an Ollama `/api/chat` request into an OpenAI chat completions request.
+pub fn transform_chat_request(ollama_body: &Value) -> Value {
+    let mut messages: Vec<Value> = Vec::new();
+    // Ollama tool results carry no call id; they answer the preceding calls in order
+    let mut pending_calls: VecDeque<(String, String)> = VecDeque::new();
+    let mut call_count = 0;
+
+    if let Some(Value::Array(ollama_messages)) = ollama_body.get("messages") {
+        for msg in ollama_messages {
+            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+            match role {
+                "assistant" => {
+                    let mut assistant_msg = json!({ "role": "assistant", "content": content });
+                    if let Some(Value::Array(tool_calls)) = msg.get("tool_calls") {
+                        pending_calls.clear();
+                        let openai_calls: Vec<Value> = tool_calls
+                            .iter()
+                            .map(|tc| {
+                                call_count += 1;
+                                let id = format!("call_{}", call_count);
+                                let name = tc
+                                    .pointer("/function/name")
+                                    .and_then(|n| n.as_str())
+                                    .unwrap_or("");
+                                pending_calls.push_back((id.clone(), name.to_string()));
+                                let arguments = tc
+                                    .pointer("/function/arguments")
+                                    .cloned()
+                                    .unwrap_or(json!({}));
+                                json!({
+                                    "id": id,
+                                    "type": "function",
+                                    "function": {
+                                        "name": name,
+                                        "arguments": serde_json::to_string(&arguments).unwrap_or_default()
+                                    }
+                                })
+                            })
+                            .collect();
+                        if !openai_calls.is_empty() {
+                            assistant_msg["tool_calls"] = json!(openai_calls);
+                        }
+                    }
+                    messages.push(assistant_msg);
+                }
+                "tool" => {
+                    let tool_name = msg.get("tool_name").and_then(|n| n.as_str());
+                    let position = tool_name
+                        .and_then(|name| pending_calls.iter().position(|(_, n)| n == name))
+                        .unwrap_or(0);
+                    let id = pending_calls
+                        .remove(position)
+                        .map(|(id, _)| id)
+                        .unwrap_or_default();
+                    messages.push(json!({
+                        "role": "tool",
+                        "tool_call_id": id,
+                        "content": content
+                    }));
+                }
+                _ => messages.push(user_message(role, content, msg.get("images"))),
+            }
+        }
+    }
+
+    let mut openai_body = json!({
+        "model": ollama_body.get("model").cloned().unwrap_or(json!("")),
+        "messages": messages,
+    });
+    if let Some(tools) = ollama_body.get("tools").filter(|t| !t.is_null()) {
+        // Ollama tools already use the OpenAI function shape
+        openai_body["tools"] = tools.clone();
+    }
+    apply_common_options(ollama_body, &mut openai_body);
+
+    debug!(openai_body = %openai_body, "Transformed Ollama chat → OpenAI request");
+    openai_body
+}
+
+/// Transforms an Ollama `/api/generate` request into an OpenAI chat completions request.
+pub fn transform_generate_request(ollama_body: &Value) -> Value {
+    let mut messages: Vec<Value> = Vec::new();
+    if let Some(system) = ollama_body.get("system").and_then(|s| s.as_str()) {
+        if !system.is_empty() {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+    }
+    let prompt = ollama_body
+        .get("prompt")
+        .and_then(|p| p.as_str())
+        .unwrap_or("");
+    messages.push(user_message("user", prompt, ollama_body.get("images")));
+
+    let mut openai_body = json!({
+        "model": ollama_body.get("model").cloned().unwrap_or(json!("")),
+        "messages": messages,
+    });
+    apply_common_options(ollama_body, &mut openai_body);
+
+    debug!(openai_body = %openai_body, "Transformed Ollama generate → OpenAI request");
+    openai_body
+}
+
+/// Ollama streams unless `stream` is explicitly `false`.
+pub fn is_stream(ollama_body: &Value) -> bool {
+    ollama_body
+        .get("stream")
+        .and_then(|s| s.as_bool())
+        .unwrap_or(true)
+}
+
+/// Builds a user (or system) message; base64 images become data URLs.
+fn user_message(role: &str, content: &str, images: Option<&Value>) -> Value {
+    let images: Vec<&str> = images
+        .and_then(|i| i.as_array())
+        .map(|i| i.iter().filter_map(|image| image.as_str()).collect())
+        .unwrap_or_default();
+    if images.is_empty() {
+        return json!({ "role": role, "content": content });
+    }
+
+    let mut parts = vec![json!({ "type": "text", "text": content })];
+    for data in images {
+        parts.push(json!({
+            "type": "image_url",
+            "image_url": { "url": format!("data:{};base64,{}", image_media_type(data), data) }
+        }));
+    }
+    json!({ "role": role, "content": parts })
+}
+
+/// Ollama sends raw base64 without a media type; recognise it from the leading bytes.
+fn image_media_type(data: &str) -> &'static str {
+    if data.starts_with("iVBOR") {
+        "image/png"
+    } else if data.starts_with("R0lG") {
+        "image/gif"
+    } else if data.starts_with("UklG") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// Maps `stream`, `format`, `think` and the sampling `options` shared by both endpoints.
+fn apply_common_options(ollama_body: &Value, openai_body: &mut Value) {
+    let stream = is_stream(ollama_body);
+    openai_body["stream"] = json!(stream);
+    if stream {
+        openai_body["stream_options"] = json!({ "include_usage": true });
+    }
+
+    if let Some(options) = ollama_body.get("options") {
+        for (ollama_key, openai_key) in [
+            ("temperature", "temperature"),
+            ("top_p", "top_p"),
+            ("num_predict", "max_tokens"),
+            ("stop", "stop"),
+            ("seed", "seed"),
+            ("frequency_penalty", "frequency_penalty"),
+            ("presence_penalty", "presence_penalty"),
+        ] {
+            if let Some(value) = options.get(ollama_key).filter(|v| !v.is_null()) {
+                openai_body[openai_key] = value.clone();
+            }
+        }
+        // A negative num_predict means "no limit"
+        if openai_body
+            .get("max_tokens")
+            .and_then(|t| t.as_i64())
+            .is_some_and(|t| t < 0)
+        {
+            if let Some(body) = openai_body.as_object_mut() {
+                body.remove("max_tokens");
+            }
+        }
+    }
+
+    // Structured output: "json" or a JSON schema
+    match ollama_body.get("format") {
+        Some(Value::String(format)) if format == "json" => {
+            openai_body["response_format"] = json!({ "type": "json_object" });
+        }
+        Some(schema @ Value::Object(_)) => {
+            openai_body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema }
+            });
+        }
+        _ => {}
+    }
+
+    // Thinking levels map onto reasoning effort
+    if let Some(level) = ollama_body.get("think").and_then(|t| t.as_str()) {
+        openai_body["reasoning_effort"] = json!(level);
+    }
+}
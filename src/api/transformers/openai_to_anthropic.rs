@@ -1,6 +1,9 @@
 use crate::common::content_utils::extract_text_from_blocks;
+use crate::core::profile::budget_for_effort;
+use crate::core::signing::ThinkingSigner;
+use crate::core::usage::Usage;
 use serde_json::{json, Value};
-use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use uuid::Uuid;
 
 const MESSAGE_ID_LENGTH: usize = 24;
@@ -22,8 +25,8 @@ fn map_stop_reason(finish_reason: &str) -> &'static str {
 /// - Regular text content
 /// - Tool calls (tool_use blocks)
 /// - Tool results (converted from OpenAI tool messages)
-/// - Thinking/reasoning content
-pub fn transform_response(openai_response: &Value, model: &str) -> Value {
+/// - Thinking/reasoning content, signed by `signer`
+pub fn transform_response(openai_response: &Value, model: &str, signer: &ThinkingSigner) -> Value {
     let msg_id = format!("msg_{}", &Uuid::new_v4().to_string().replace('-', "")[..MESSAGE_ID_LENGTH]);
 
     let choice = openai_response
@@ -43,7 +46,11 @@ pub fn transform_response(openai_response: &Value, model: &str) -> Value {
         if !reasoning.is_empty() {
             content_blocks.push(json!({
                 "type": "thinking",
-                "thinking": reasoning
+                "thinking": reasoning,
+                "signature": signer.sign(
+                    reasoning,
+                    message.get("reasoning_signature").and_then(|s| s.as_str())
+                )
             }));
         }
     }
@@ -162,15 +169,19 @@ pub struct StreamTransformer {
     started: bool,
     input_tokens: u64,
     output_tokens: u64,
+    cached_tokens: u64,
     last_finish_reason: Option<String>,
     tool_call_index: Option<i32>,
     in_text_block: bool,
     finished: bool,
     thinking_content: String,
+    /// Signature the upstream gave the current thinking block, if it signs them
+    upstream_signature: Option<String>,
+    signer: Arc<ThinkingSigner>,
 }
 
 impl StreamTransformer {
-    pub fn new(model: &str) -> Self {
+    pub fn new(model: &str, signer: Arc<ThinkingSigner>) -> Self {
         Self {
             model: model.to_string(),
             msg_id: format!("msg_{}", &Uuid::new_v4().to_string().replace('-', "")[..MESSAGE_ID_LENGTH]),
@@ -187,11 +198,23 @@ impl StreamTransformer {
             started: false,
             input_tokens: 0,
             output_tokens: 0,
+            cached_tokens: 0,
             last_finish_reason: None,
             tool_call_index: None,
             in_text_block: false,
             finished: false,
             thinking_content: String::new(),
+            upstream_signature: None,
+            signer,
+        }
+    }
+
+    /// Token usage reported by the upstream so far
+    pub fn usage(&self) -> Usage {
+        Usage {
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            cached_tokens: self.cached_tokens,
         }
     }
 
@@ -271,6 +294,13 @@ impl StreamTransformer {
             if let Some(ct) = usage.get("completion_tokens").and_then(|t| t.as_u64()) {
                 self.output_tokens = ct;
             }
+            if let Some(cached) = usage
+                .get("prompt_tokens_details")
+                .and_then(|d| d.get("cached_tokens"))
+                .and_then(|t| t.as_u64())
+            {
+                self.cached_tokens = cached;
+            }
         }
 
         let choices = match chunk.get("choices").and_then(|c| c.as_array()) {
@@ -292,6 +322,9 @@ impl StreamTransformer {
             if let Some(reasoning) = delta.get("reasoning_content").and_then(|r| r.as_str()) {
                 events.extend(self.process_thinking_content(reasoning));
             }
+            if let Some(signature) = delta.get("reasoning_signature").and_then(|s| s.as_str()) {
+                self.upstream_signature = Some(signature.to_string());
+            }
 
             // Handle tool calls
             if let Some(Value::Array(tool_calls)) = delta.get("tool_calls") {
@@ -565,7 +598,10 @@ impl StreamTransformer {
         let mut events = Vec::new();
         if self.in_thinking {
             // Add signature_delta before closing thinking block
-            let signature = generate_signature(&self.thinking_content);
+            let signature = self.signer.sign(
+                &self.thinking_content,
+                self.upstream_signature.take().as_deref(),
+            );
             events.push(format_sse(
                 "content_block_delta",
                 &json!({
@@ -604,18 +640,6 @@ fn format_sse(event_type: &str, data: &Value) -> String {
     )
 }
 
-/// Generate a signature for the thinking content block.
-/// This signature is used to verify the integrity of the thinking blob.
-/// It's a base64-encoded SHA256 hash of the thinking content.
-fn generate_signature(thinking_content: &str) -> String {
-    use base64::{engine::general_purpose::STANDARD, Engine};
-
-    let mut hasher = Sha256::new();
-    hasher.update(thinking_content.as_bytes());
-    let hash = hasher.finalize();
-    STANDARD.encode(hash)
-}
-
 /// Strip leading tags that the model sometimes hallucinated at the start of its content block.
 pub fn strip_hallucinated_tags(content: &str) -> String {
     const TAGS: [&str; 12] = [
@@ -653,3 +677,358 @@ pub fn strip_hallucinated_tags(content: &str) -> String {
 
     result.trim_start().to_string()
 }
+
+/// Upper bound sent when an OpenAI request leaves `max_tokens` unset; Anthropic requires one.
+const DEFAULT_MAX_TOKENS: u64 = 4096;
+
+/// Smallest `budget_tokens` Anthropic accepts.
+const MIN_THINKING_BUDGET: u64 = 1024;
+
+/// Transforms an OpenAI chat completions request into an Anthropic Messages API request,
+/// for providers declared with `kind: anthropic`.
+pub fn transform_request(openai_body: &Value) -> Value {
+    let mut system_parts: Vec<String> = Vec::new();
+    let mut messages: Vec<Value> = Vec::new();
+
+    if let Some(Value::Array(openai_messages)) = openai_body.get("messages") {
+        for msg in openai_messages {
+            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            match role {
+                "system" | "developer" => {
+                    let text = message_text(msg.get("content"));
+                    if !text.is_empty() {
+                        system_parts.push(text);
+                    }
+                }
+                "assistant" => {
+                    let mut blocks: Vec<Value> = Vec::new();
+                    // Anthropic only takes back thinking it signed itself
+                    let reasoning = msg.get("reasoning_content").and_then(|r| r.as_str());
+                    let signature = msg.get("reasoning_signature").and_then(|s| s.as_str());
+                    if let (Some(reasoning), Some(signature)) = (reasoning, signature) {
+                        blocks.push(json!({
+                            "type": "thinking",
+                            "thinking": reasoning,
+                            "signature": signature
+                        }));
+                    }
+                    let text = message_text(msg.get("content"));
+                    if !text.is_empty() {
+                        blocks.push(json!({ "type": "text", "text": text }));
+                    }
+                    if let Some(Value::Array(tool_calls)) = msg.get("tool_calls") {
+                        for tc in tool_calls {
+                            let func = tc.get("function").cloned().unwrap_or(json!({}));
+                            let args_str = func
+                                .get("arguments")
+                                .and_then(|a| a.as_str())
+                                .unwrap_or("{}");
+                            blocks.push(json!({
+                                "type": "tool_use",
+                                "id": tc.get("id").cloned().unwrap_or(json!("")),
+                                "name": func.get("name").cloned().unwrap_or(json!("")),
+                                "input": serde_json::from_str::<Value>(args_str).unwrap_or(json!({}))
+                            }));
+                        }
+                    }
+                    if !blocks.is_empty() {
+                        push_message(&mut messages, "assistant", blocks);
+                    }
+                }
+                "tool" => {
+                    // Images returned by the tool stay inside the tool_result
+                    let content = match msg.get("content") {
+                        Some(Value::Array(parts)) => {
+                            json!(parts
+                                .iter()
+                                .filter_map(convert_user_part)
+                                .collect::<Vec<_>>())
+                        }
+                        other => json!(message_text(other)),
+                    };
+                    let mut block = json!({
+                        "type": "tool_result",
+                        "tool_use_id": msg.get("tool_call_id").cloned().unwrap_or(json!("")),
+                        "content": content
+                    });
+                    if msg.get("is_error").and_then(|e| e.as_bool()) == Some(true) {
+                        block["is_error"] = json!(true);
+                    }
+                    push_message(&mut messages, "user", vec![block]);
+                }
+                _ => {
+                    let blocks = match msg.get("content") {
+                        Some(Value::String(text)) => vec![json!({ "type": "text", "text": text })],
+                        Some(Value::Array(parts)) => {
+                            parts.iter().filter_map(convert_user_part).collect()
+                        }
+                        _ => Vec::new(),
+                    };
+                    if !blocks.is_empty() {
+                        push_message(&mut messages, "user", blocks);
+                    }
+                }
+            }
+        }
+    }
+
+    let requested_max_tokens = openai_body
+        .get("max_completion_tokens")
+        .or_else(|| openai_body.get("max_tokens"))
+        .and_then(|t| t.as_u64());
+    let mut max_tokens = requested_max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+
+    let thinking = match openai_body
+        .get("thinking")
+        .filter(|t| t.get("type").is_some())
+    {
+        Some(thinking) => Some(thinking.clone()),
+        None => openai_body
+            .get("reasoning_effort")
+            .and_then(|e| e.as_str())
+            .and_then(budget_for_effort)
+            .and_then(|budget| {
+                // The budget counts toward max_tokens, which has to leave room for the answer
+                let budget = match requested_max_tokens {
+                    Some(requested) => budget.min(requested.saturating_sub(1)),
+                    None => {
+                        max_tokens += budget;
+                        budget
+                    }
+                };
+                (budget >= MIN_THINKING_BUDGET)
+                    .then(|| json!({ "type": "enabled", "budget_tokens": budget }))
+            }),
+    };
+
+    let mut anthropic_body = json!({
+        "model": openai_body.get("model").cloned().unwrap_or(json!("")),
+        "messages": messages,
+        "max_tokens": max_tokens,
+        "stream": openai_body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false),
+    });
+    if !system_parts.is_empty() {
+        anthropic_body["system"] = json!(system_parts.join("\n\n"));
+    }
+
+    // Pass through optional parameters; Anthropic's temperature tops out at 1
+    if let Some(temp) = openai_body.get("temperature").and_then(|t| t.as_f64()) {
+        anthropic_body["temperature"] = json!(temp.min(1.0));
+    }
+    if let Some(top_p) = openai_body.get("top_p").filter(|v| !v.is_null()) {
+        anthropic_body["top_p"] = top_p.clone();
+    }
+    match openai_body.get("stop") {
+        Some(Value::String(stop)) => anthropic_body["stop_sequences"] = json!([stop]),
+        Some(Value::Array(stop)) => anthropic_body["stop_sequences"] = json!(stop),
+        _ => {}
+    }
+    if let Some(user) = openai_body.get("user").and_then(|u| u.as_str()) {
+        anthropic_body["metadata"] = json!({ "user_id": user });
+    }
+    if let Some(thinking) = thinking {
+        if thinking.get("type").and_then(|t| t.as_str()) != Some("disabled") {
+            // Anthropic rejects sampling changes while thinking
+            if let Some(body) = anthropic_body.as_object_mut() {
+                body.remove("temperature");
+                body.remove("top_p");
+            }
+        }
+        anthropic_body["thinking"] = thinking;
+    }
+
+    // Tools
+    if let Some(Value::Array(tools)) = openai_body.get("tools") {
+        let anthropic_tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| tool.get("function"))
+            .map(|func| {
+                json!({
+                    "name": func.get("name").cloned().unwrap_or(json!("")),
+                    "description": func.get("description").cloned().unwrap_or(json!("")),
+                    "input_schema": func
+                        .get("parameters")
+                        .cloned()
+                        .unwrap_or(json!({ "type": "object", "properties": {} })),
+                })
+            })
+            .collect();
+        if !anthropic_tools.is_empty() {
+            anthropic_body["tools"] = json!(anthropic_tools);
+
+            // Tool choice
+            let mut tool_choice = match openai_body.get("tool_choice") {
+                Some(Value::String(choice)) if choice == "required" => json!({ "type": "any" }),
+                Some(Value::String(choice)) if choice == "none" => json!({ "type": "none" }),
+                Some(choice) if choice.get("function").is_some() => json!({
+                    "type": "tool",
+                    "name": choice.pointer("/function/name").cloned().unwrap_or(json!(""))
+                }),
+                _ => json!({ "type": "auto" }),
+            };
+            if openai_body.get("parallel_tool_calls") == Some(&json!(false)) {
+                tool_choice["disable_parallel_tool_use"] = json!(true);
+            }
+            anthropic_body["tool_choice"] = tool_choice;
+        }
+    }
+
+    anthropic_body
+}
+
+/// Appends content blocks, merging into the previous message when the role repeats;
+/// Anthropic expects user and assistant turns to alternate.
+fn push_message(messages: &mut Vec<Value>, role: &str, blocks: Vec<Value>) {
+    if let Some(last) = messages.last_mut() {
+        if last.get("role").and_then(|r| r.as_str()) == Some(role) {
+            if let Some(content) = last.get_mut("content").and_then(|c| c.as_array_mut()) {
+                content.extend(blocks);
+                return;
+            }
+        }
+    }
+    messages.push(json!({
+        "role": role,
+        "content": blocks
+    }));
+}
+
+/// Flattens OpenAI message content (a string or text parts) into plain text.
+fn message_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => extract_text_from_blocks(parts),
+        _ => String::new(),
+    }
+}
+
+fn convert_user_part(part: &Value) -> Option<Value> {
+    match part.get("type").and_then(|t| t.as_str()).unwrap_or("text") {
+        "text" => Some(json!({
+            "type": "text",
+            "text": part.get("text").cloned().unwrap_or(json!(""))
+        })),
+        "image_url" => {
+            let url = part.pointer("/image_url/url").and_then(|u| u.as_str())?;
+            let source = match url
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))
+            {
+                Some((media_type, data)) => json!({
+                    "type": "base64",
+                    "media_type": media_type,
+                    "data": data
+                }),
+                None => json!({ "type": "url", "url": url }),
+            };
+            Some(json!({ "type": "image", "source": source }))
+        }
+        "file" => {
+            let data = part.pointer("/file/file_data").and_then(|d| d.as_str())?;
+            let (media_type, data) = data
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))?;
+            let mut document = json!({
+                "type": "document",
+                "source": { "type": "base64", "media_type": media_type, "data": data }
+            });
+            if let Some(filename) = part.pointer("/file/filename") {
+                document["title"] = filename.clone();
+            }
+            Some(document)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transformers::anthropic_to_openai;
+
+    /// A client's follow-up turn replaying `assistant` as history.
+    fn follow_up(assistant: Value) -> Value {
+        json!({
+            "model": "claude",
+            "max_tokens": 100,
+            "messages": [
+                { "role": "user", "content": "hi" },
+                { "role": "assistant", "content": assistant },
+                { "role": "user", "content": "again" }
+            ]
+        })
+    }
+
+    #[test]
+    fn upstream_thinking_signatures_survive_a_client_round_trip() {
+        let signer = ThinkingSigner::new(Some("secret"));
+        let upstream_response = json!({
+            "id": "msg_1",
+            "model": "claude",
+            "content": [
+                { "type": "thinking", "thinking": "hmm", "signature": "EqQB" },
+                { "type": "text", "text": "Hi" }
+            ],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 1, "output_tokens": 1 }
+        });
+        let openai = anthropic_to_openai::transform_response(&upstream_response, true);
+        let client_response = transform_response(&openai, "claude", &signer);
+        let content = client_response["content"].clone();
+        assert_eq!(content[0]["thinking"], "hmm");
+
+        let openai = anthropic_to_openai::transform_request(&follow_up(content.clone()), &signer);
+        let upstream_request = transform_request(&openai);
+        assert_eq!(
+            upstream_request["messages"][1]["content"],
+            json!([
+                { "type": "thinking", "thinking": "hmm", "signature": "EqQB" },
+                { "type": "text", "text": "Hi" }
+            ])
+        );
+
+        // Edited thinking no longer verifies and is not replayed
+        let mut edited = content;
+        edited[0]["thinking"] = json!("hmm, edited");
+        let openai = anthropic_to_openai::transform_request(&follow_up(edited), &signer);
+        assert_eq!(
+            transform_request(&openai)["messages"][1]["content"],
+            json!([{ "type": "text", "text": "Hi" }])
+        );
+    }
+
+    #[test]
+    fn reasoning_effort_becomes_a_thinking_budget() {
+        let request = |extra: Value| {
+            let mut body = json!({
+                "model": "claude",
+                "temperature": 0.5,
+                "messages": [{ "role": "user", "content": "hi" }]
+            });
+            body.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            transform_request(&body)
+        };
+
+        let body = request(json!({ "reasoning_effort": "low" }));
+        assert_eq!(
+            body["thinking"],
+            json!({ "type": "enabled", "budget_tokens": 4096 })
+        );
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS + 4096);
+        assert!(body.get("temperature").is_none());
+
+        let body = request(json!({ "reasoning_effort": "high", "max_tokens": 2000 }));
+        assert_eq!(
+            body["thinking"],
+            json!({ "type": "enabled", "budget_tokens": 1999 })
+        );
+        assert_eq!(body["max_tokens"], 2000);
+
+        // Too little room left for the minimum budget
+        let body = request(json!({ "reasoning_effort": "low", "max_tokens": 1000 }));
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["temperature"], 0.5);
+    }
+}
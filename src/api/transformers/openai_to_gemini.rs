@@ -0,0 +1,300 @@
+use crate::common::content_utils::extract_text_from_blocks;
+use crate::common::tool_ids::split_tool_call_id;
+use crate::core::profile::budget_for_effort;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::debug;
+
+/// Transforms an OpenAI chat completions request into a Gemini `generateContent` request,
+/// for providers declared with `kind: gemini`.
+///
+/// The model and the streaming flag travel in the URL, so neither appears in the body.
+pub fn transform_request(openai_body: &Value) -> Value {
+    let mut system_parts: Vec<Value> = Vec::new();
+    let mut contents: Vec<Value> = Vec::new();
+    // Gemini answers function calls by name, OpenAI by call id
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+
+    if let Some(Value::Array(messages)) = openai_body.get("messages") {
+        for msg in messages {
+            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            match role {
+                "system" | "developer" => {
+                    let text = message_text(msg.get("content"));
+                    if !text.is_empty() {
+                        system_parts.push(json!({ "text": text }));
+                    }
+                }
+                "assistant" => {
+                    let mut parts: Vec<Value> = Vec::new();
+                    let text = message_text(msg.get("content"));
+                    if !text.is_empty() {
+                        parts.push(json!({ "text": text }));
+                    }
+                    if let Some(Value::Array(tool_calls)) = msg.get("tool_calls") {
+                        for tc in tool_calls {
+                            let func = tc.get("function").cloned().unwrap_or(json!({}));
+                            let name = func.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                            if let Some(id) = tc.get("id").and_then(|i| i.as_str()) {
+                                tool_names.insert(id.to_string(), name.to_string());
+                            }
+                            let args_str = func
+                                .get("arguments")
+                                .and_then(|a| a.as_str())
+                                .unwrap_or("{}");
+                            let mut part = json!({
+                                "functionCall": {
+                                    "name": name,
+                                    "args": serde_json::from_str::<Value>(args_str).unwrap_or(json!({}))
+                                }
+                            });
+                            // Returned as Gemini gave it, or Gemini 3 rejects the turn
+                            let signature = tc
+                                .get("id")
+                                .and_then(|i| i.as_str())
+                                .and_then(|id| split_tool_call_id(id).1);
+                            if let Some(signature) = signature {
+                                part["thoughtSignature"] = json!(signature);
+                            }
+                            parts.push(part);
+                        }
+                    }
+                    if !parts.is_empty() {
+                        push_content(&mut contents, "model", parts);
+                    }
+                }
+                "tool" => {
+                    let id = msg
+                        .get("tool_call_id")
+                        .and_then(|i| i.as_str())
+                        .unwrap_or("");
+                    let name = tool_names.get(id).cloned().unwrap_or_default();
+                    let text = message_text(msg.get("content"));
+                    let is_error = msg.get("is_error").and_then(|e| e.as_bool()) == Some(true);
+                    // Structured results are passed as-is; anything else is wrapped
+                    let response = match serde_json::from_str::<Value>(&text) {
+                        Ok(Value::Object(object)) => Value::Object(object),
+                        _ if is_error => json!({ "error": text }),
+                        _ => json!({ "content": text }),
+                    };
+                    let mut parts = vec![json!({
+                        "functionResponse": {
+                            "name": name,
+                            "response": response
+                        }
+                    })];
+                    // Images returned by the tool follow its response in the same turn
+                    if let Some(Value::Array(content)) = msg.get("content") {
+                        parts.extend(
+                            content
+                                .iter()
+                                .filter(|p| {
+                                    p.get("type").and_then(|t| t.as_str()) == Some("image_url")
+                                })
+                                .filter_map(convert_user_part),
+                        );
+                    }
+                    push_content(&mut contents, "user", parts);
+                }
+                _ => {
+                    let parts = match msg.get("content") {
+                        Some(Value::String(text)) => vec![json!({ "text": text })],
+                        Some(Value::Array(parts)) => {
+                            parts.iter().filter_map(convert_user_part).collect()
+                        }
+                        _ => Vec::new(),
+                    };
+                    if !parts.is_empty() {
+                        push_content(&mut contents, "user", parts);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut gemini_body = json!({ "contents": contents });
+    if !system_parts.is_empty() {
+        gemini_body["systemInstruction"] = json!({ "parts": system_parts });
+    }
+
+    // Generation parameters
+    let mut generation_config = json!({});
+    if let Some(temp) = openai_body.get("temperature").filter(|v| !v.is_null()) {
+        generation_config["temperature"] = temp.clone();
+    }
+    if let Some(top_p) = openai_body.get("top_p").filter(|v| !v.is_null()) {
+        generation_config["topP"] = top_p.clone();
+    }
+    if let Some(max_tokens) = openai_body
+        .get("max_completion_tokens")
+        .or_else(|| openai_body.get("max_tokens"))
+        .filter(|v| !v.is_null())
+    {
+        generation_config["maxOutputTokens"] = max_tokens.clone();
+    }
+    if let Some(n) = openai_body.get("n").filter(|v| !v.is_null()) {
+        generation_config["candidateCount"] = n.clone();
+    }
+    match openai_body.get("stop") {
+        Some(Value::String(stop)) => generation_config["stopSequences"] = json!([stop]),
+        Some(Value::Array(stop)) => generation_config["stopSequences"] = json!(stop),
+        _ => {}
+    }
+    if let Some(format) = openai_body.get("response_format") {
+        match format.get("type").and_then(|t| t.as_str()) {
+            Some("json_object") => {
+                generation_config["responseMimeType"] = json!("application/json");
+            }
+            Some("json_schema") => {
+                generation_config["responseMimeType"] = json!("application/json");
+                if let Some(schema) = format.pointer("/json_schema/schema") {
+                    generation_config["responseJsonSchema"] = schema.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(thinking_config) = thinking_config(openai_body) {
+        generation_config["thinkingConfig"] = thinking_config;
+    }
+    if generation_config.as_object().is_some_and(|c| !c.is_empty()) {
+        gemini_body["generationConfig"] = generation_config;
+    }
+
+    // Tools
+    if let Some(Value::Array(tools)) = openai_body.get("tools") {
+        let declarations: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| tool.get("function"))
+            .map(|func| {
+                let mut declaration = json!({
+                    "name": func.get("name").cloned().unwrap_or(json!("")),
+                    "description": func.get("description").cloned().unwrap_or(json!("")),
+                });
+                if let Some(parameters) = func.get("parameters").filter(|p| !p.is_null()) {
+                    declaration["parametersJsonSchema"] = parameters.clone();
+                }
+                declaration
+            })
+            .collect();
+        if !declarations.is_empty() {
+            gemini_body["tools"] = json!([{ "functionDeclarations": declarations }]);
+
+            // Tool choice
+            let function_calling_config = match openai_body.get("tool_choice") {
+                Some(Value::String(choice)) if choice == "none" => json!({ "mode": "NONE" }),
+                Some(Value::String(choice)) if choice == "required" => json!({ "mode": "ANY" }),
+                Some(choice) if choice.get("function").is_some() => json!({
+                    "mode": "ANY",
+                    "allowedFunctionNames": [choice.pointer("/function/name").cloned().unwrap_or(json!(""))]
+                }),
+                _ => json!({ "mode": "AUTO" }),
+            };
+            gemini_body["toolConfig"] = json!({ "functionCallingConfig": function_calling_config });
+        }
+    }
+
+    debug!(gemini_body = %gemini_body, "Transformed OpenAI → Gemini request");
+    gemini_body
+}
+
+/// `thinkingConfig` for `reasoning_effort`, or for the `thinking` object `/v1/messages` keeps;
+/// thoughts are included so they come back as `reasoning_content`.
+fn thinking_config(openai_body: &Value) -> Option<Value> {
+    let thinking = openai_body.get("thinking");
+    let budget: i64 = match thinking.and_then(|t| t.get("type")?.as_str()) {
+        Some("enabled") => thinking
+            .and_then(|t| t.get("budget_tokens")?.as_i64())
+            .unwrap_or(-1),
+        // -1 lets the model pick its budget
+        Some("adaptive") => -1,
+        Some("disabled") => 0,
+        _ => match openai_body.get("reasoning_effort")?.as_str()? {
+            "none" => 0,
+            effort => budget_for_effort(effort)? as i64,
+        },
+    };
+    Some(json!({ "thinkingBudget": budget, "includeThoughts": budget != 0 }))
+}
+
+/// Appends parts, merging into the previous turn when the role repeats.
+fn push_content(contents: &mut Vec<Value>, role: &str, parts: Vec<Value>) {
+    if let Some(last) = contents.last_mut() {
+        if last.get("role").and_then(|r| r.as_str()) == Some(role) {
+            if let Some(existing) = last.get_mut("parts").and_then(|p| p.as_array_mut()) {
+                existing.extend(parts);
+                return;
+            }
+        }
+    }
+    contents.push(json!({
+        "role": role,
+        "parts": parts
+    }));
+}
+
+/// Flattens OpenAI message content (a string or text parts) into plain text.
+fn message_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => extract_text_from_blocks(parts),
+        _ => String::new(),
+    }
+}
+
+fn convert_user_part(part: &Value) -> Option<Value> {
+    match part.get("type").and_then(|t| t.as_str()).unwrap_or("text") {
+        "text" => Some(json!({ "text": part.get("text").cloned().unwrap_or(json!("")) })),
+        "image_url" => {
+            let url = part.pointer("/image_url/url").and_then(|u| u.as_str())?;
+            match url
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))
+            {
+                Some((mime_type, data)) => Some(json!({
+                    "inlineData": {
+                        "mimeType": mime_type,
+                        "data": data
+                    }
+                })),
+                None => Some(json!({
+                    "fileData": {
+                        "mimeType": image_mime_type(url),
+                        "fileUri": url
+                    }
+                })),
+            }
+        }
+        "file" => {
+            let data = part.pointer("/file/file_data").and_then(|d| d.as_str())?;
+            let (mime_type, data) = data
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split_once(";base64,"))?;
+            Some(json!({
+                "inlineData": {
+                    "mimeType": mime_type,
+                    "data": data
+                }
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Guesses an image's MIME type from its URL; Gemini requires one for `fileData`.
+fn image_mime_type(url: &str) -> &'static str {
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(url)
+        .to_ascii_lowercase();
+    if path.ends_with(".png") {
+        "image/png"
+    } else if path.ends_with(".webp") {
+        "image/webp"
+    } else if path.ends_with(".gif") {
+        "image/gif"
+    } else {
+        "image/jpeg"
+    }
+}
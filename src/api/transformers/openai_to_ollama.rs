@@ -0,0 +1,315 @@
+use crate::core::usage::Usage;
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// Which Ollama endpoint a response is rendered for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// `/api/chat`: text under `message.content`, tool calls allowed.
+    Chat,
+    /// `/api/generate`: text under `response`.
+    Generate,
+}
+
+/// Map OpenAI finish_reason to Ollama done_reason
+fn map_done_reason(finish_reason: &str) -> &'static str {
+    match finish_reason {
+        "length" => "length",
+        _ => "stop",
+    }
+}
+
+fn created_at() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+/// OpenAI tool calls with JSON-string arguments → Ollama tool calls with object arguments
+fn convert_tool_calls(tool_calls: &[Value]) -> Vec<Value> {
+    tool_calls
+        .iter()
+        .map(|tc| {
+            let args_str = tc
+                .pointer("/function/arguments")
+                .and_then(|a| a.as_str())
+                .unwrap_or("{}");
+            json!({
+                "function": {
+                    "name": tc.pointer("/function/name").cloned().unwrap_or(json!("")),
+                    "arguments": serde_json::from_str::<Value>(args_str).unwrap_or(json!({}))
+                }
+            })
+        })
+        .collect()
+}
+
+/// Transform a non-streaming OpenAI completion into an Ollama response.
+///
+/// Ollama reports durations in nanoseconds; only the total is known here, so it is
+/// attributed to evaluation.
+pub fn transform_response(
+    openai_response: &Value,
+    model: &str,
+    endpoint: Endpoint,
+    elapsed: Duration,
+) -> Value {
+    let message = openai_response
+        .pointer("/choices/0/message")
+        .cloned()
+        .unwrap_or(json!({}));
+    let content = message
+        .get("content")
+        .and_then(|c| c.as_str())
+        .unwrap_or("");
+    let thinking = message
+        .get("reasoning_content")
+        .and_then(|r| r.as_str())
+        .filter(|r| !r.is_empty());
+    let done_reason = openai_response
+        .pointer("/choices/0/finish_reason")
+        .and_then(|f| f.as_str())
+        .map(map_done_reason)
+        .unwrap_or("stop");
+    let usage = openai_response
+        .get("usage")
+        .map(Usage::from_openai)
+        .unwrap_or_default();
+
+    let mut response = json!({
+        "model": model,
+        "created_at": created_at(),
+    });
+    match endpoint {
+        Endpoint::Chat => {
+            let mut ollama_message = json!({ "role": "assistant", "content": content });
+            if let Some(thinking) = thinking {
+                ollama_message["thinking"] = json!(thinking);
+            }
+            if let Some(Value::Array(tool_calls)) = message.get("tool_calls") {
+                ollama_message["tool_calls"] = json!(convert_tool_calls(tool_calls));
+            }
+            response["message"] = ollama_message;
+        }
+        Endpoint::Generate => {
+            response["response"] = json!(content);
+            if let Some(thinking) = thinking {
+                response["thinking"] = json!(thinking);
+            }
+        }
+    }
+    add_final_fields(&mut response, done_reason, &usage, elapsed, Duration::ZERO);
+    response
+}
+
+fn add_final_fields(
+    response: &mut Value,
+    done_reason: &str,
+    usage: &Usage,
+    total: Duration,
+    prompt_eval: Duration,
+) {
+    response["done"] = json!(true);
+    response["done_reason"] = json!(done_reason);
+    response["total_duration"] = json!(nanos(total));
+    response["load_duration"] = json!(0);
+    response["prompt_eval_count"] = json!(usage.input_tokens);
+    response["prompt_eval_duration"] = json!(nanos(prompt_eval));
+    response["eval_count"] = json!(usage.output_tokens);
+    response["eval_duration"] = json!(nanos(total.saturating_sub(prompt_eval)));
+}
+
+/// State machine for transforming streaming OpenAI SSE chunks into Ollama's
+/// newline-delimited JSON stream.
+///
+/// Tool call arguments arrive in pieces and are only sent once complete, in a line of
+/// their own before the final `done: true` line.
+pub struct StreamTransformer {
+    model: String,
+    endpoint: Endpoint,
+    started_at: Instant,
+    first_token_at: Option<Instant>,
+    input_tokens: u64,
+    output_tokens: u64,
+    cached_tokens: u64,
+    last_finish_reason: Option<String>,
+    /// (name, accumulated arguments) per OpenAI tool call index
+    tool_calls: Vec<(String, String)>,
+    finished: bool,
+}
+
+impl StreamTransformer {
+    pub fn new(model: &str, endpoint: Endpoint) -> Self {
+        Self {
+            model: model.to_string(),
+            endpoint,
+            started_at: Instant::now(),
+            first_token_at: None,
+            input_tokens: 0,
+            output_tokens: 0,
+            cached_tokens: 0,
+            last_finish_reason: None,
+            tool_calls: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Token usage reported by the upstream so far
+    pub fn usage(&self) -> Usage {
+        Usage {
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            cached_tokens: self.cached_tokens,
+        }
+    }
+
+    /// Process a single OpenAI SSE data payload and return NDJSON lines
+    pub fn process_chunk(&mut self, data: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if data.trim() == "[DONE]" {
+            return self.finish();
+        }
+
+        let chunk: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(_) => return lines,
+        };
+
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            let usage = Usage::from_openai(usage);
+            self.input_tokens = usage.input_tokens;
+            self.output_tokens = usage.output_tokens;
+            self.cached_tokens = usage.cached_tokens;
+        }
+
+        let Some(choice) = chunk.pointer("/choices/0") else {
+            return lines;
+        };
+        if let Some(fr) = choice.get("finish_reason").and_then(|f| f.as_str()) {
+            self.last_finish_reason = Some(fr.to_string());
+        }
+        let Some(delta) = choice.get("delta") else {
+            return lines;
+        };
+
+        if let Some(reasoning) = delta
+            .get("reasoning_content")
+            .and_then(|r| r.as_str())
+            .filter(|r| !r.is_empty())
+        {
+            lines.push(self.partial_line("", Some(reasoning)));
+        }
+        if let Some(content) = delta
+            .get("content")
+            .and_then(|c| c.as_str())
+            .filter(|c| !c.is_empty())
+        {
+            lines.push(self.partial_line(content, None));
+        }
+        if let Some(Value::Array(tool_calls)) = delta.get("tool_calls") {
+            for tc in tool_calls {
+                let index = tc.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+                if self.tool_calls.len() <= index {
+                    self.tool_calls
+                        .resize(index + 1, (String::new(), String::new()));
+                }
+                let (name, arguments) = &mut self.tool_calls[index];
+                if let Some(n) = tc.pointer("/function/name").and_then(|n| n.as_str()) {
+                    name.push_str(n);
+                }
+                if let Some(a) = tc.pointer("/function/arguments").and_then(|a| a.as_str()) {
+                    arguments.push_str(a);
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// Sends any buffered tool calls and the final `done: true` line, once
+    pub fn finish(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.finished {
+            return lines;
+        }
+        self.finished = true;
+
+        if self.endpoint == Endpoint::Chat && !self.tool_calls.is_empty() {
+            let tool_calls: Vec<Value> = self
+                .tool_calls
+                .iter()
+                .map(|(name, arguments)| {
+                    json!({
+                        "function": {
+                            "name": name,
+                            "arguments": serde_json::from_str::<Value>(arguments).unwrap_or(json!({}))
+                        }
+                    })
+                })
+                .collect();
+            let mut line = self.base_line("", None);
+            line["message"]["tool_calls"] = json!(tool_calls);
+            line["done"] = json!(false);
+            lines.push(format_line(&line));
+        }
+
+        let now = Instant::now();
+        let prompt_eval = self
+            .first_token_at
+            .unwrap_or(now)
+            .duration_since(self.started_at);
+        let done_reason = self
+            .last_finish_reason
+            .as_deref()
+            .map(map_done_reason)
+            .unwrap_or("stop");
+        let mut line = self.base_line("", None);
+        add_final_fields(
+            &mut line,
+            done_reason,
+            &self.usage(),
+            now.duration_since(self.started_at),
+            prompt_eval,
+        );
+        lines.push(format_line(&line));
+        lines
+    }
+
+    fn partial_line(&mut self, content: &str, thinking: Option<&str>) -> String {
+        self.first_token_at.get_or_insert_with(Instant::now);
+        let mut line = self.base_line(content, thinking);
+        line["done"] = json!(false);
+        format_line(&line)
+    }
+
+    fn base_line(&self, content: &str, thinking: Option<&str>) -> Value {
+        let mut line = json!({
+            "model": self.model,
+            "created_at": created_at(),
+        });
+        match self.endpoint {
+            Endpoint::Chat => {
+                let mut message = json!({ "role": "assistant", "content": content });
+                if let Some(thinking) = thinking {
+                    message["thinking"] = json!(thinking);
+                }
+                line["message"] = message;
+            }
+            Endpoint::Generate => {
+                line["response"] = json!(content);
+                if let Some(thinking) = thinking {
+                    line["thinking"] = json!(thinking);
+                }
+            }
+        }
+        line
+    }
+}
+
+fn format_line(data: &Value) -> String {
+    format!("{}\n", serde_json::to_string(data).unwrap_or_default())
+}
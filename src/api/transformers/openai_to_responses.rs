@@ -0,0 +1,543 @@
+use crate::core::usage::Usage;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+fn new_id(prefix: &str) -> String {
+    format!("{}_{}", prefix, Uuid::new_v4().simple())
+}
+
+fn now_unix() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// Map OpenAI finish_reason to the Responses status and incomplete reason
+fn map_status(finish_reason: Option<&str>) -> (&'static str, Value) {
+    match finish_reason {
+        Some("length") => ("incomplete", json!({ "reason": "max_output_tokens" })),
+        Some("content_filter") => ("incomplete", json!({ "reason": "content_filter" })),
+        _ => ("completed", Value::Null),
+    }
+}
+
+fn usage_json(usage: &Usage, reasoning_tokens: u64) -> Value {
+    json!({
+        "input_tokens": usage.input_tokens,
+        "input_tokens_details": { "cached_tokens": usage.cached_tokens },
+        "output_tokens": usage.output_tokens,
+        "output_tokens_details": { "reasoning_tokens": reasoning_tokens },
+        "total_tokens": usage.total()
+    })
+}
+
+fn reasoning_tokens(usage: &Value) -> u64 {
+    usage
+        .get("completion_tokens_details")
+        .and_then(|d| d.get("reasoning_tokens"))
+        .and_then(|t| t.as_u64())
+        .unwrap_or(0)
+}
+
+/// Response object with the request's settings echoed back, as the Responses API does.
+fn response_object(request: &Value, id: &str, model: &str, created_at: i64) -> Value {
+    let echo = |key: &str, default: Value| request.get(key).cloned().unwrap_or(default);
+    json!({
+        "id": id,
+        "object": "response",
+        "created_at": created_at,
+        "status": "in_progress",
+        "error": null,
+        "incomplete_details": null,
+        "instructions": echo("instructions", Value::Null),
+        "max_output_tokens": echo("max_output_tokens", Value::Null),
+        "model": model,
+        "output": [],
+        "parallel_tool_calls": echo("parallel_tool_calls", json!(true)),
+        "previous_response_id": null,
+        "reasoning": echo("reasoning", json!({ "effort": null, "summary": null })),
+        "store": false,
+        "temperature": echo("temperature", json!(1.0)),
+        "text": echo("text", json!({ "format": { "type": "text" } })),
+        "tool_choice": echo("tool_choice", json!("auto")),
+        "tools": echo("tools", json!([])),
+        "top_p": echo("top_p", json!(1.0)),
+        "usage": null,
+        "metadata": echo("metadata", json!({}))
+    })
+}
+
+fn reasoning_item(id: &str, text: &str) -> Value {
+    json!({
+        "type": "reasoning",
+        "id": id,
+        "summary": [{ "type": "summary_text", "text": text }]
+    })
+}
+
+fn output_text_part(text: &str) -> Value {
+    json!({
+        "type": "output_text",
+        "text": text,
+        "annotations": []
+    })
+}
+
+fn message_item(id: &str, status: &str, content: Vec<Value>) -> Value {
+    json!({
+        "type": "message",
+        "id": id,
+        "status": status,
+        "role": "assistant",
+        "content": content
+    })
+}
+
+fn function_call_item(id: &str, call_id: &str, name: &str, arguments: &str, status: &str) -> Value {
+    json!({
+        "type": "function_call",
+        "id": id,
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments,
+        "status": status
+    })
+}
+
+/// Transform a non-streaming OpenAI completion response into a Responses API response object.
+pub fn transform_response(openai_response: &Value, model: &str, request: &Value) -> Value {
+    let choice = openai_response
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .and_then(|a| a.first())
+        .cloned()
+        .unwrap_or(json!({}));
+    let message = choice.get("message").cloned().unwrap_or(json!({}));
+    let finish_reason = choice.get("finish_reason").and_then(|f| f.as_str());
+
+    let mut output: Vec<Value> = Vec::new();
+
+    // Thinking/reasoning content
+    if let Some(reasoning) = message.get("reasoning_content").and_then(|r| r.as_str()) {
+        if !reasoning.is_empty() {
+            output.push(reasoning_item(&new_id("rs"), reasoning));
+        }
+    }
+
+    let tool_calls = message
+        .get("tool_calls")
+        .and_then(|t| t.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    // Text content; always present when there is nothing else to return
+    let text = message
+        .get("content")
+        .and_then(|c| c.as_str())
+        .unwrap_or("");
+    if !text.is_empty() || tool_calls.is_empty() {
+        output.push(message_item(
+            &new_id("msg"),
+            "completed",
+            vec![output_text_part(text)],
+        ));
+    }
+
+    // Tool calls
+    for tc in &tool_calls {
+        let func = tc.get("function").cloned().unwrap_or(json!({}));
+        let call_id = tc
+            .get("id")
+            .and_then(|i| i.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| new_id("call"));
+        output.push(function_call_item(
+            &new_id("fc"),
+            &call_id,
+            func.get("name").and_then(|n| n.as_str()).unwrap_or(""),
+            func.get("arguments")
+                .and_then(|a| a.as_str())
+                .unwrap_or("{}"),
+            "completed",
+        ));
+    }
+
+    let usage = openai_response.get("usage").cloned().unwrap_or(json!({}));
+    let (status, incomplete_details) = map_status(finish_reason);
+
+    let mut response = response_object(request, &new_id("resp"), model, now_unix());
+    response["status"] = json!(status);
+    response["incomplete_details"] = incomplete_details;
+    response["output"] = json!(output);
+    response["usage"] = usage_json(&Usage::from_openai(&usage), reasoning_tokens(&usage));
+    response
+}
+
+/// The output item currently being streamed.
+enum OpenItem {
+    Reasoning {
+        id: String,
+        text: String,
+    },
+    Message {
+        id: String,
+        text: String,
+    },
+    FunctionCall {
+        id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+}
+
+/// State machine for transforming streaming OpenAI SSE events into Responses API SSE events.
+pub struct StreamTransformer {
+    response: Value,
+    sequence_number: u64,
+    output: Vec<Value>,
+    current: Option<OpenItem>,
+    usage: Usage,
+    reasoning_tokens: u64,
+    last_finish_reason: Option<String>,
+    finished: bool,
+}
+
+impl StreamTransformer {
+    pub fn new(model: &str, request: &Value) -> Self {
+        Self {
+            response: response_object(request, &new_id("resp"), model, now_unix()),
+            sequence_number: 0,
+            output: Vec::new(),
+            current: None,
+            usage: Usage::default(),
+            reasoning_tokens: 0,
+            last_finish_reason: None,
+            finished: false,
+        }
+    }
+
+    /// Token usage reported by the upstream so far
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// Returns the initial response.created and response.in_progress events
+    pub fn start_events(&mut self) -> Vec<String> {
+        let response = self.response.clone();
+        vec![
+            self.event("response.created", json!({ "response": response })),
+            self.event("response.in_progress", json!({ "response": response })),
+        ]
+    }
+
+    /// Process a single OpenAI SSE delta chunk and return Responses SSE events
+    pub fn process_chunk(&mut self, data: &str) -> Vec<String> {
+        if data.trim() == "[DONE]" {
+            return self.finish();
+        }
+
+        let chunk: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+
+        // Extract usage info if present
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            self.usage = Usage::from_openai(usage);
+            self.reasoning_tokens = reasoning_tokens(usage);
+        }
+
+        let mut events = Vec::new();
+        let choices = match chunk.get("choices").and_then(|c| c.as_array()) {
+            Some(c) => c,
+            None => return events,
+        };
+
+        for choice in choices {
+            if let Some(fr) = choice.get("finish_reason").and_then(|f| f.as_str()) {
+                self.last_finish_reason = Some(fr.to_string());
+            }
+            let delta = match choice.get("delta") {
+                Some(d) => d,
+                None => continue,
+            };
+
+            if let Some(reasoning) = delta.get("reasoning_content").and_then(|r| r.as_str()) {
+                if !reasoning.is_empty() {
+                    events.extend(self.process_reasoning(reasoning));
+                }
+            }
+
+            if let Some(content) = delta.get("content").and_then(|c| c.as_str()) {
+                if !content.is_empty() {
+                    events.extend(self.process_text(content));
+                }
+            }
+
+            if let Some(Value::Array(tool_calls)) = delta.get("tool_calls") {
+                for tc in tool_calls {
+                    events.extend(self.process_tool_call(tc));
+                }
+            }
+        }
+
+        events
+    }
+
+    fn output_index(&self) -> usize {
+        self.output.len()
+    }
+
+    fn process_reasoning(&mut self, reasoning: &str) -> Vec<String> {
+        let mut events = Vec::new();
+        if !matches!(self.current, Some(OpenItem::Reasoning { .. })) {
+            events.extend(self.close_current_item());
+            let id = new_id("rs");
+            let output_index = self.output_index();
+            events.push(self.event(
+                "response.output_item.added",
+                json!({
+                    "output_index": output_index,
+                    "item": { "type": "reasoning", "id": id, "summary": [] }
+                }),
+            ));
+            events.push(self.event(
+                "response.reasoning_summary_part.added",
+                json!({
+                    "item_id": id,
+                    "output_index": output_index,
+                    "summary_index": 0,
+                    "part": { "type": "summary_text", "text": "" }
+                }),
+            ));
+            self.current = Some(OpenItem::Reasoning {
+                id,
+                text: String::new(),
+            });
+        }
+
+        let output_index = self.output_index();
+        if let Some(OpenItem::Reasoning { id, text }) = &mut self.current {
+            text.push_str(reasoning);
+            let id = id.clone();
+            events.push(self.event(
+                "response.reasoning_summary_text.delta",
+                json!({
+                    "item_id": id,
+                    "output_index": output_index,
+                    "summary_index": 0,
+                    "delta": reasoning
+                }),
+            ));
+        }
+        events
+    }
+
+    fn process_text(&mut self, content: &str) -> Vec<String> {
+        let mut events = Vec::new();
+        if !matches!(self.current, Some(OpenItem::Message { .. })) {
+            events.extend(self.close_current_item());
+            let id = new_id("msg");
+            let output_index = self.output_index();
+            events.push(self.event(
+                "response.output_item.added",
+                json!({
+                    "output_index": output_index,
+                    "item": message_item(&id, "in_progress", Vec::new())
+                }),
+            ));
+            events.push(self.event(
+                "response.content_part.added",
+                json!({
+                    "item_id": id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "part": output_text_part("")
+                }),
+            ));
+            self.current = Some(OpenItem::Message {
+                id,
+                text: String::new(),
+            });
+        }
+
+        let output_index = self.output_index();
+        if let Some(OpenItem::Message { id, text }) = &mut self.current {
+            text.push_str(content);
+            let id = id.clone();
+            events.push(self.event(
+                "response.output_text.delta",
+                json!({
+                    "item_id": id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "delta": content
+                }),
+            ));
+        }
+        events
+    }
+
+    fn process_tool_call(&mut self, tc: &Value) -> Vec<String> {
+        let mut events = Vec::new();
+        let func = tc.get("function").cloned().unwrap_or(json!({}));
+
+        // New tool call starting
+        if let Some(call_id) = tc.get("id").and_then(|i| i.as_str()) {
+            events.extend(self.close_current_item());
+            let id = new_id("fc");
+            let name = func
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or("")
+                .to_string();
+            let output_index = self.output_index();
+            events.push(self.event(
+                "response.output_item.added",
+                json!({
+                    "output_index": output_index,
+                    "item": function_call_item(&id, call_id, &name, "", "in_progress")
+                }),
+            ));
+            self.current = Some(OpenItem::FunctionCall {
+                id,
+                call_id: call_id.to_string(),
+                name,
+                arguments: String::new(),
+            });
+        }
+
+        // Tool call argument delta
+        if let Some(args) = func.get("arguments").and_then(|a| a.as_str()) {
+            let output_index = self.output_index();
+            if let Some(OpenItem::FunctionCall { id, arguments, .. }) = &mut self.current {
+                if !args.is_empty() {
+                    arguments.push_str(args);
+                    let id = id.clone();
+                    events.push(self.event(
+                        "response.function_call_arguments.delta",
+                        json!({
+                            "item_id": id,
+                            "output_index": output_index,
+                            "delta": args
+                        }),
+                    ));
+                }
+            }
+        }
+        events
+    }
+
+    fn close_current_item(&mut self) -> Vec<String> {
+        let mut events = Vec::new();
+        let output_index = self.output_index();
+        let item = match self.current.take() {
+            None => return events,
+            Some(OpenItem::Reasoning { id, text }) => {
+                events.push(self.event(
+                    "response.reasoning_summary_text.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "summary_index": 0,
+                        "text": text
+                    }),
+                ));
+                events.push(self.event(
+                    "response.reasoning_summary_part.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "summary_index": 0,
+                        "part": { "type": "summary_text", "text": text }
+                    }),
+                ));
+                reasoning_item(&id, &text)
+            }
+            Some(OpenItem::Message { id, text }) => {
+                events.push(self.event(
+                    "response.output_text.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "text": text
+                    }),
+                ));
+                events.push(self.event(
+                    "response.content_part.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "part": output_text_part(&text)
+                    }),
+                ));
+                message_item(&id, "completed", vec![output_text_part(&text)])
+            }
+            Some(OpenItem::FunctionCall {
+                id,
+                call_id,
+                name,
+                arguments,
+            }) => {
+                events.push(self.event(
+                    "response.function_call_arguments.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "arguments": arguments
+                    }),
+                ));
+                function_call_item(&id, &call_id, &name, &arguments, "completed")
+            }
+        };
+
+        events.push(self.event(
+            "response.output_item.done",
+            json!({
+                "output_index": output_index,
+                "item": item
+            }),
+        ));
+        self.output.push(item);
+        events
+    }
+
+    /// Closes any open item and sends the final response.completed (or response.incomplete) event
+    pub fn finish(&mut self) -> Vec<String> {
+        // If stream was already properly finished with [DONE], don't send duplicate events
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+
+        let mut events = self.close_current_item();
+
+        let (status, incomplete_details) = map_status(self.last_finish_reason.as_deref());
+        let mut response = self.response.clone();
+        response["status"] = json!(status);
+        response["incomplete_details"] = incomplete_details;
+        response["output"] = json!(self.output);
+        response["usage"] = usage_json(&self.usage, self.reasoning_tokens);
+
+        let event_type = if status == "completed" {
+            "response.completed"
+        } else {
+            "response.incomplete"
+        };
+        events.push(self.event(event_type, json!({ "response": response })));
+        events
+    }
+
+    /// Formats one SSE event, stamping its type and sequence number
+    fn event(&mut self, event_type: &str, mut data: Value) -> String {
+        data["type"] = json!(event_type);
+        data["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        format!(
+            "event: {}\ndata: {}\n\n",
+            event_type,
+            serde_json::to_string(&data).unwrap_or_default()
+        )
+    }
+}
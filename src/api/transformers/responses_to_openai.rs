@@ -0,0 +1,322 @@
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+/// Transforms an OpenAI Responses API request into a chat completions request for the upstream API.
+pub fn transform_request(responses_body: &Value) -> Value {
+    let model = responses_body.get("model").cloned().unwrap_or(json!(""));
+    let stream = responses_body
+        .get("stream")
+        .cloned()
+        .unwrap_or(json!(false));
+
+    let mut messages: Vec<Value> = Vec::new();
+
+    // Instructions become the system message
+    if let Some(instructions) = responses_body.get("instructions").and_then(|i| i.as_str()) {
+        if !instructions.is_empty() {
+            messages.push(json!({
+                "role": "system",
+                "content": instructions
+            }));
+        }
+    }
+
+    match responses_body.get("input") {
+        Some(Value::String(text)) => {
+            messages.push(json!({
+                "role": "user",
+                "content": text
+            }));
+        }
+        Some(Value::Array(items)) => {
+            for item in items {
+                convert_input_item(item, &mut messages);
+            }
+        }
+        _ => {}
+    }
+
+    let mut openai_body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": stream,
+    });
+
+    // Pass through optional parameters
+    if let Some(max_tokens) = responses_body
+        .get("max_output_tokens")
+        .filter(|v| !v.is_null())
+    {
+        openai_body["max_tokens"] = max_tokens.clone();
+    }
+    for key in ["temperature", "top_p", "parallel_tool_calls", "user"] {
+        if let Some(value) = responses_body.get(key).filter(|v| !v.is_null()) {
+            openai_body[key] = value.clone();
+        }
+    }
+
+    // Tools; only function tools can be expressed in chat completions
+    if let Some(Value::Array(tools)) = responses_body.get("tools") {
+        let openai_tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| {
+                let tool_type = tool.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                if tool_type != "function" {
+                    warn!(tool_type = tool_type, "Dropping unsupported Responses tool");
+                    return None;
+                }
+                let mut function = json!({
+                    "name": tool.get("name").cloned().unwrap_or(json!("")),
+                    "description": tool.get("description").cloned().unwrap_or(json!("")),
+                    "parameters": tool.get("parameters").cloned().unwrap_or(json!({})),
+                });
+                if let Some(strict) = tool.get("strict").filter(|s| !s.is_null()) {
+                    function["strict"] = strict.clone();
+                }
+                Some(json!({
+                    "type": "function",
+                    "function": function
+                }))
+            })
+            .collect();
+        if !openai_tools.is_empty() {
+            openai_body["tools"] = json!(openai_tools);
+
+            // Tool choice
+            match responses_body.get("tool_choice") {
+                Some(Value::String(choice)) => openai_body["tool_choice"] = json!(choice),
+                Some(choice) if choice.get("type").and_then(|t| t.as_str()) == Some("function") => {
+                    if let Some(name) = choice.get("name").and_then(|n| n.as_str()) {
+                        openai_body["tool_choice"] = json!({
+                            "type": "function",
+                            "function": { "name": name }
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Reasoning effort
+    if let Some(effort) = responses_body
+        .get("reasoning")
+        .and_then(|r| r.get("effort"))
+        .filter(|e| !e.is_null())
+    {
+        openai_body["reasoning_effort"] = effort.clone();
+    }
+
+    // Structured output
+    if let Some(format) = responses_body.get("text").and_then(|t| t.get("format")) {
+        match format.get("type").and_then(|t| t.as_str()) {
+            Some("json_schema") => {
+                let mut json_schema = json!({
+                    "name": format.get("name").cloned().unwrap_or(json!("response")),
+                    "schema": format.get("schema").cloned().unwrap_or(json!({})),
+                });
+                if let Some(strict) = format.get("strict").filter(|s| !s.is_null()) {
+                    json_schema["strict"] = strict.clone();
+                }
+                if let Some(description) = format.get("description") {
+                    json_schema["description"] = description.clone();
+                }
+                openai_body["response_format"] = json!({
+                    "type": "json_schema",
+                    "json_schema": json_schema
+                });
+            }
+            Some("json_object") => {
+                openai_body["response_format"] = json!({ "type": "json_object" });
+            }
+            _ => {}
+        }
+    }
+
+    // Stream options
+    if stream.as_bool().unwrap_or(false) {
+        openai_body["stream_options"] = json!({ "include_usage": true });
+    }
+
+    debug!(openai_body = %openai_body, "Transformed Responses → OpenAI request");
+    openai_body
+}
+
+fn convert_input_item(item: &Value, messages: &mut Vec<Value>) {
+    let item_type = item
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("message");
+    match item_type {
+        "message" => {
+            let role = match item.get("role").and_then(|r| r.as_str()).unwrap_or("user") {
+                "developer" => "system",
+                role => role,
+            };
+            let content = match item.get("content") {
+                Some(Value::String(text)) => json!(text),
+                Some(Value::Array(parts)) if role == "user" => json!(convert_user_parts(parts)),
+                Some(Value::Array(parts)) => json!(parts
+                    .iter()
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("")),
+                _ => json!(""),
+            };
+            messages.push(json!({
+                "role": role,
+                "content": content
+            }));
+        }
+        "function_call" => {
+            let tool_call = json!({
+                "id": item.get("call_id").cloned().unwrap_or(json!("")),
+                "type": "function",
+                "function": {
+                    "name": item.get("name").cloned().unwrap_or(json!("")),
+                    "arguments": item.get("arguments").cloned().unwrap_or(json!("{}"))
+                }
+            });
+
+            // Calls following an assistant message belong to that same chat message
+            match messages.last_mut() {
+                Some(last) if last.get("role").and_then(|r| r.as_str()) == Some("assistant") => {
+                    match last.get_mut("tool_calls").and_then(|t| t.as_array_mut()) {
+                        Some(tool_calls) => tool_calls.push(tool_call),
+                        None => last["tool_calls"] = json!([tool_call]),
+                    }
+                }
+                _ => messages.push(json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [tool_call]
+                })),
+            }
+        }
+        "function_call_output" => {
+            let output = match item.get("output") {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Array(parts)) => parts
+                    .iter()
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            };
+            messages.push(json!({
+                "role": "tool",
+                "tool_call_id": item.get("call_id").cloned().unwrap_or(json!("")),
+                "content": output
+            }));
+        }
+        "reasoning" => {
+            // Skip reasoning items in history; chat completions has no input field for them
+        }
+        other => {
+            warn!(
+                item_type = other,
+                "Dropping unsupported Responses input item"
+            );
+        }
+    }
+}
+
+fn convert_user_parts(parts: &[Value]) -> Vec<Value> {
+    parts
+        .iter()
+        .filter_map(
+            |part| match part.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+                "input_text" | "output_text" | "text" => Some(json!({
+                    "type": "text",
+                    "text": part.get("text").cloned().unwrap_or(json!(""))
+                })),
+                "input_image" => {
+                    let url = part.get("image_url").and_then(|u| u.as_str())?;
+                    let mut image_url = json!({ "url": url });
+                    if let Some(detail) = part.get("detail").filter(|d| !d.is_null()) {
+                        image_url["detail"] = detail.clone();
+                    }
+                    Some(json!({
+                        "type": "image_url",
+                        "image_url": image_url
+                    }))
+                }
+                "input_file" => {
+                    let mut file = json!({});
+                    for key in ["file_data", "file_id", "filename"] {
+                        if let Some(value) = part.get(key) {
+                            file[key] = value.clone();
+                        }
+                    }
+                    Some(json!({
+                        "type": "file",
+                        "file": file
+                    }))
+                }
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transformers::openai_to_responses;
+
+    #[test]
+    fn output_items_round_trip_as_chat_history() {
+        let request = json!({
+            "model": "gpt",
+            "instructions": "be brief",
+            "input": "weather in Paris?",
+            "max_output_tokens": 100,
+            "reasoning": { "effort": "low" }
+        });
+        let completion = json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "Checking.",
+                    "reasoning_content": "need a tool",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" }
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+        });
+        let response = openai_to_responses::transform_response(&completion, "gpt", &request);
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["max_output_tokens"], 100);
+
+        // The client sends the output back as input, followed by the tool's result
+        let mut input = vec![json!({ "role": "user", "content": "weather in Paris?" })];
+        input.extend(response["output"].as_array().unwrap().iter().cloned());
+        input.push(json!({ "type": "function_call_output", "call_id": "call_1", "output": "20C" }));
+        let follow_up = transform_request(&json!({
+            "model": "gpt",
+            "instructions": "be brief",
+            "input": input,
+            "reasoning": { "effort": "low" }
+        }));
+        assert_eq!(
+            follow_up["messages"],
+            json!([
+                { "role": "system", "content": "be brief" },
+                { "role": "user", "content": "weather in Paris?" },
+                { "role": "assistant", "content": "Checking.", "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" }
+                }] },
+                { "role": "tool", "tool_call_id": "call_1", "content": "20C" }
+            ])
+        );
+        assert_eq!(follow_up["reasoning_effort"], "low");
+    }
+}
@@ -0,0 +1,64 @@
+/// Minimal glob matcher supporting `*` (any run of characters) and `?` (any single character).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<usize> = None;
+    let mut star_ti = 0;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some(pi);
+            star_ti = ti;
+            pi += 1;
+        } else if let Some(s) = star {
+            // Backtrack: let the last `*` swallow one more character
+            pi = s + 1;
+            star_ti += 1;
+            ti = star_ti;
+        } else {
+            return false;
+        }
+    }
+
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn literals_must_match_exactly() {
+        assert!(glob_match("gpt-4o", "gpt-4o"));
+        assert!(!glob_match("gpt-4o", "gpt-4o-mini"));
+        assert!(!glob_match("gpt-4o", "gpt-4"));
+        assert!(glob_match("", ""));
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob_match("gpt-*", "gpt-"));
+        assert!(glob_match("gpt-*", "gpt-4o-mini"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("claude-*-sonnet*", "claude-3-5-sonnet-latest"));
+        assert!(!glob_match("claude-*-sonnet", "claude-3-5-sonnet-latest"));
+        // The first `*` has to backtrack past an early "-mini"
+        assert!(glob_match("*-mini", "gpt-4o-mini-mini"));
+        assert!(!glob_match("gpt-*", "o1-gpt-4"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_match("o?-mini", "o3-mini"));
+        assert!(!glob_match("o?-mini", "o-mini"));
+        assert!(glob_match("??", "çğ"));
+    }
+}
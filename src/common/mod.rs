@@ -1 +1,4 @@
 pub mod content_utils;
+pub mod glob;
+pub mod sse;
+pub mod tool_ids;
@@ -0,0 +1,134 @@
+use bytes::Bytes;
+use futures::{stream, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use tracing::error;
+
+/// Cuts a byte stream into complete lines without decoding it, so a multibyte UTF-8
+/// character split across two network chunks is never seen half.
+#[derive(Default)]
+pub struct LineSplitter {
+    buffer: Vec<u8>,
+}
+
+impl LineSplitter {
+    /// Adds a chunk and returns the lines it completed, each with its `\n`.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            lines.push(self.buffer.drain(..=pos).collect());
+        }
+        lines
+    }
+
+    /// Whatever is left after the stream ended without a final newline.
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        Some(std::mem::take(&mut self.buffer)).filter(|rest| !rest.is_empty())
+    }
+}
+
+/// Payload of a `data:` line, if the (complete) line is one.
+pub fn data_payload(line: &[u8]) -> Option<String> {
+    let line = String::from_utf8_lossy(line);
+    line.trim_end_matches(['\r', '\n'])
+        .strip_prefix("data:")
+        .map(|data| data.trim().to_string())
+}
+
+/// Splits an upstream SSE byte stream into its raw lines, each with its `\n`, for routes
+/// that forward the stream byte for byte.
+///
+/// A read error is logged and ends the stream. Whatever is left in the buffer when the
+/// upstream closes is flushed as well.
+pub fn raw_lines<S>(byte_stream: S) -> Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + Unpin + 'static,
+{
+    struct State<S> {
+        stream: S,
+        lines: LineSplitter,
+        pending: VecDeque<Vec<u8>>,
+        done: bool,
+    }
+
+    let state = State {
+        stream: byte_stream,
+        lines: LineSplitter::default(),
+        pending: VecDeque::new(),
+        done: false,
+    };
+
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(line) = state.pending.pop_front() {
+                return Some((line, state));
+            }
+            if state.done {
+                return None;
+            }
+
+            match state.stream.next().await {
+                Some(Ok(chunk)) => state.pending.extend(state.lines.push(&chunk)),
+                Some(Err(e)) => {
+                    error!(error = %e, "Stream read error");
+                    state.done = true;
+                }
+                None => {
+                    // Process any remaining buffer
+                    state.pending.extend(state.lines.finish());
+                    state.done = true;
+                }
+            }
+        }
+    }))
+}
+
+/// Splits an upstream SSE byte stream into the payloads of its `data:` lines.
+pub fn data_lines<S>(byte_stream: S) -> Pin<Box<dyn Stream<Item = String> + Send>>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + Unpin + 'static,
+{
+    Box::pin(raw_lines(byte_stream).filter_map(|line| async move { data_payload(&line) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(parts: Vec<Vec<u8>>) -> Vec<String> {
+        let chunks = parts.into_iter().map(|p| Ok(Bytes::from(p)));
+        data_lines(stream::iter(chunks)).collect().await
+    }
+
+    #[tokio::test]
+    async fn multibyte_characters_split_across_chunks_survive() {
+        let text = "data: {\"t\":\"ğüş 你好 🎉\"}\n\n".as_bytes();
+        // Cut inside the emoji's four bytes
+        let (first, second) = text.split_at(text.len() - 6);
+        assert_eq!(
+            collect(vec![first.to_vec(), second.to_vec()]).await,
+            vec!["{\"t\":\"ğüş 你好 🎉\"}"]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_data_lines_are_yielded_and_tail_is_flushed() {
+        let parts = vec![
+            b": keepalive\n\nevent: x\r\ndata: a\r\n\r\nda".to_vec(),
+            b"ta: b\n\ndata: [DONE]".to_vec(),
+        ];
+        assert_eq!(collect(parts).await, vec!["a", "b", "[DONE]"]);
+    }
+
+    #[test]
+    fn splitter_keeps_line_endings() {
+        let mut lines = LineSplitter::default();
+        assert!(lines.push(b"data: 1").is_empty());
+        assert_eq!(
+            lines.push(b"\n\n"),
+            vec![b"data: 1\n".to_vec(), b"\n".to_vec()]
+        );
+        assert_eq!(lines.finish(), None);
+    }
+}
@@ -0,0 +1,55 @@
+//! Tool call ids that carry a Gemini thought signature.
+//!
+//! Gemini wants a `functionCall` part's `thoughtSignature` back with the call in later turns,
+//! and the id is the one thing every client returns unchanged, so the signature travels in it.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+/// Separates a tool call id from the thought signature carried after it.
+const THOUGHT_SIGNATURE_MARKER: &str = "__ts_";
+
+/// Appends `signature` to `id`, re-encoded to stay within Anthropic's `[a-zA-Z0-9_-]` ids.
+pub fn encode_tool_call_id(id: &str, signature: Option<&str>) -> String {
+    match signature {
+        Some(signature) => format!(
+            "{}{}{}",
+            id,
+            THOUGHT_SIGNATURE_MARKER,
+            URL_SAFE_NO_PAD.encode(signature)
+        ),
+        None => id.to_string(),
+    }
+}
+
+/// Splits an id made by `encode_tool_call_id` into the plain id and the thought signature, if any.
+pub fn split_tool_call_id(id: &str) -> (&str, Option<String>) {
+    match id.split_once(THOUGHT_SIGNATURE_MARKER) {
+        Some((id, signature)) => (
+            id,
+            URL_SAFE_NO_PAD
+                .decode(signature)
+                .ok()
+                .and_then(|s| String::from_utf8(s).ok()),
+        ),
+        None => (id, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_round_trip_through_ids() {
+        let id = encode_tool_call_id("call_1", Some("CiQB+/a9Zz=="));
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+        assert_eq!(
+            split_tool_call_id(&id),
+            ("call_1", Some("CiQB+/a9Zz==".to_string()))
+        );
+        assert_eq!(encode_tool_call_id("call_2", None), "call_2");
+        assert_eq!(split_tool_call_id("call_2"), ("call_2", None));
+    }
+}
@@ -0,0 +1,319 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::core::usage::{Usage, UsageScope};
+
+/// One line of the usage log.
+#[derive(Debug, Serialize, Deserialize)]
+struct UsageEntry {
+    timestamp: String,
+    day: NaiveDate,
+    client: String,
+    model: String,
+    /// Routing entry label; absent in lines written before it was recorded.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    route: String,
+    provider: String,
+    upstream_model: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    #[serde(default)]
+    cached_tokens: u64,
+    /// Estimated cost; absent when the upstream model has no configured price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cost_usd: Option<f64>,
+}
+
+/// Totals are kept per routing entry rather than per requested model name, so clients
+/// making up model names cannot grow the map without bound.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct UsageKey {
+    pub client: String,
+    pub route: String,
+    pub day: NaiveDate,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, usage: &Usage, cost_usd: Option<f64>) {
+        self.requests += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.cached_tokens += usage.cached_tokens;
+        self.cost_usd += cost_usd.unwrap_or(0.0);
+    }
+
+    pub fn merge(&mut self, other: &UsageTotals) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Per-client, per-route, per-day token usage backed by an append-only JSONL file.
+///
+/// The file is replayed on startup so totals (and therefore budgets) survive restarts.
+pub struct UsageLedger {
+    file: Mutex<Option<File>>,
+    totals: Mutex<HashMap<UsageKey, UsageTotals>>,
+}
+
+impl UsageLedger {
+    pub fn open(path: &Path) -> Self {
+        let mut totals: HashMap<UsageKey, UsageTotals> = HashMap::new();
+
+        if let Ok(existing) = File::open(path) {
+            let mut skipped = 0;
+            for line in BufReader::new(existing).lines().map_while(Result::ok) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<UsageEntry>(&line) {
+                    Ok(entry) => {
+                        let usage = Usage {
+                            input_tokens: entry.input_tokens,
+                            output_tokens: entry.output_tokens,
+                            cached_tokens: entry.cached_tokens,
+                        };
+                        // Lines from before routes were recorded count under their model
+                        let route = if entry.route.is_empty() {
+                            entry.model
+                        } else {
+                            entry.route
+                        };
+                        totals
+                            .entry(UsageKey {
+                                client: entry.client,
+                                route,
+                                day: entry.day,
+                            })
+                            .or_default()
+                            .add(&usage, entry.cost_usd);
+                    }
+                    Err(_) => skipped += 1,
+                }
+            }
+            if skipped > 0 {
+                warn!(skipped, path = %path.display(), "Skipped unreadable usage log lines");
+            }
+            info!(entries = totals.len(), path = %path.display(), "Loaded usage log");
+        }
+
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let file = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(f) => Some(f),
+            Err(e) => {
+                error!(error = %e, path = %path.display(), "Cannot open usage log, usage will not be persisted");
+                None
+            }
+        };
+
+        Self {
+            file: Mutex::new(file),
+            totals: Mutex::new(totals),
+        }
+    }
+
+    pub fn record(&self, scope: &UsageScope, usage: &Usage, cost_usd: Option<f64>) {
+        let now = Utc::now();
+        let entry = UsageEntry {
+            timestamp: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            day: now.date_naive(),
+            client: scope.client.clone(),
+            model: scope.model.clone(),
+            route: scope.route.clone(),
+            provider: scope.provider.clone(),
+            upstream_model: scope.upstream_model.clone(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cached_tokens: usage.cached_tokens,
+            cost_usd,
+        };
+
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let line = serde_json::to_string(&entry).unwrap_or_default();
+            if let Err(e) = writeln!(file, "{}", line) {
+                error!(error = %e, "Failed to append to usage log");
+            }
+        }
+
+        self.totals
+            .lock()
+            .unwrap()
+            .entry(UsageKey {
+                client: entry.client,
+                route: entry.route,
+                day: entry.day,
+            })
+            .or_default()
+            .add(usage, cost_usd);
+    }
+
+    /// A client's usage in the current calendar month (UTC).
+    pub fn month_totals(&self, client: &str) -> UsageTotals {
+        let today = Utc::now().date_naive();
+        let mut month = UsageTotals::default();
+        for (key, totals) in self.totals.lock().unwrap().iter() {
+            if key.client == client
+                && key.day.year() == today.year()
+                && key.day.month() == today.month()
+            {
+                month.merge(totals);
+            }
+        }
+        month
+    }
+
+    /// Totals per client and route for days in `from..=to`, sorted by client then route.
+    pub fn summary(
+        &self,
+        client: Option<&str>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Vec<(String, String, UsageTotals)> {
+        let mut grouped: HashMap<(String, String), UsageTotals> = HashMap::new();
+        for (key, totals) in self.totals.lock().unwrap().iter() {
+            if client.is_some_and(|c| c != key.client)
+                || from.is_some_and(|from| key.day < from)
+                || to.is_some_and(|to| key.day > to)
+            {
+                continue;
+            }
+            grouped
+                .entry((key.client.clone(), key.route.clone()))
+                .or_default()
+                .merge(totals);
+        }
+
+        let mut rows: Vec<_> = grouped
+            .into_iter()
+            .map(|((client, route), totals)| (client, route, totals))
+            .collect();
+        rows.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn log_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("usage-{}-{}.jsonl", name, std::process::id()))
+    }
+
+    fn scope(client: &str, model: &str, route: &str) -> UsageScope {
+        UsageScope {
+            client: client.to_string(),
+            model: model.to_string(),
+            route: route.to_string(),
+            provider: "openai".to_string(),
+            upstream_model: model.to_string(),
+        }
+    }
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> Usage {
+        Usage {
+            input_tokens,
+            output_tokens,
+            cached_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn log_is_replayed_on_open() {
+        let path = log_path("replay");
+        let today = Utc::now().date_naive();
+        let line = |day: NaiveDate, model: &str, route: Option<&str>, cost: f64| {
+            let mut line = json!({
+                "timestamp": "2026-01-01T00:00:00.000Z",
+                "day": day,
+                "client": "a",
+                "model": model,
+                "provider": "openai",
+                "upstream_model": model,
+                "input_tokens": 100,
+                "output_tokens": 50,
+                "cost_usd": cost
+            });
+            if let Some(route) = route {
+                line["route"] = json!(route);
+            }
+            line.to_string()
+        };
+        let lines = [
+            line(today, "gpt-a", Some("gpt-*"), 0.5),
+            line(today, "gpt-b", Some("gpt-*"), 0.25),
+            // Written before routes were recorded
+            line(today, "claude", None, 1.0),
+            line(
+                NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+                "gpt-a",
+                Some("gpt-*"),
+                9.0,
+            ),
+            "not json".to_string(),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let ledger = UsageLedger::open(&path);
+        let month = ledger.month_totals("a");
+        assert_eq!(
+            (month.requests, month.tokens(), month.cost_usd),
+            (3, 450, 1.75)
+        );
+        assert_eq!(ledger.month_totals("b").requests, 0);
+
+        let routes: Vec<(String, u64)> = ledger
+            .summary(Some("a"), Some(today), None)
+            .into_iter()
+            .map(|(_, route, totals)| (route, totals.requests))
+            .collect();
+        assert_eq!(
+            routes,
+            vec![("claude".to_string(), 1), ("gpt-*".to_string(), 2)]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recorded_usage_survives_a_restart() {
+        let path = log_path("restart");
+        let _ = fs::remove_file(&path);
+        let ledger = UsageLedger::open(&path);
+        ledger.record(&scope("a", "junk-1", "other"), &usage(10, 5), Some(0.1));
+        ledger.record(&scope("a", "junk-2", "other"), &usage(20, 5), None);
+        drop(ledger);
+
+        let ledger = UsageLedger::open(&path);
+        let rows = ledger.summary(None, None, None);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].1, "other");
+        assert_eq!(rows[0].2.tokens(), 40);
+        assert_eq!(ledger.month_totals("a").cost_usd, 0.1);
+        fs::remove_file(&path).unwrap();
+    }
+}
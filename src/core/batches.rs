@@ -0,0 +1,279 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::core::config::BatchConfig;
+use crate::core::retry::RetryPolicy;
+
+/// The only completion window OpenAI offers.
+pub const COMPLETION_WINDOW: &str = "24h";
+
+const COMPLETION_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    Failed,
+    InProgress,
+    Finalizing,
+    Completed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct BatchRequestCounts {
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// Stored state of one OpenAI batch, kept in `{id}.json`; serialises close to OpenAI's
+/// `batch` object.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Batch {
+    pub id: String,
+    /// Virtual key name that created the batch; only it can see the batch.
+    pub client: String,
+    pub endpoint: String,
+    pub input_file_id: String,
+    pub completion_window: String,
+    pub status: BatchStatus,
+    /// Validation errors of the input file, as OpenAI's `errors` list.
+    pub errors: Option<Value>,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    pub created_at: i64,
+    pub in_progress_at: Option<i64>,
+    pub expires_at: i64,
+    pub finalizing_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub failed_at: Option<i64>,
+    pub expired_at: Option<i64>,
+    pub cancelling_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub request_counts: BatchRequestCounts,
+    pub metadata: Option<Value>,
+}
+
+impl Batch {
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() >= self.expires_at
+    }
+}
+
+/// OpenAI batches kept on disk so they survive restarts.
+///
+/// Besides `{id}.json`, a running batch appends to `{id}.output.jsonl` and
+/// `{id}.errors.jsonl`; both become files in the [`FileStore`](crate::core::files::FileStore)
+/// once the batch is finalized.
+pub struct BatchStore {
+    dir: PathBuf,
+    concurrency: usize,
+    retry: RetryPolicy,
+    batches: Mutex<HashMap<String, Batch>>,
+}
+
+impl BatchStore {
+    /// Loads the `batch_*` state files; the directory is shared with message batches.
+    pub fn open(config: &BatchConfig) -> Self {
+        let dir = config.batch_dir();
+        if let Err(e) = fs::create_dir_all(&dir) {
+            error!(error = %e, dir = %dir.display(), "Cannot create batch directory");
+        }
+
+        let mut batches = HashMap::new();
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+                let is_state = path.extension().is_some_and(|ext| ext == "json")
+                    && path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with("batch_"));
+                if !is_state {
+                    continue;
+                }
+                match fs::read(&path)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<Batch>(&bytes).ok())
+                {
+                    Some(batch) => {
+                        batches.insert(batch.id.clone(), batch);
+                    }
+                    None => warn!(path = %path.display(), "Skipped unreadable batch file"),
+                }
+            }
+            info!(batches = batches.len(), dir = %dir.display(), "Loaded batches");
+        }
+
+        Self {
+            dir,
+            concurrency: config.concurrency.max(1),
+            retry: RetryPolicy::new(config.retry.clone()),
+            batches: Mutex::new(batches),
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Retry policy for single requests of a batch.
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Stores a new batch in `validating`; the runner checks its input file.
+    pub fn create(
+        &self,
+        client: &str,
+        input_file_id: &str,
+        endpoint: &str,
+        metadata: Option<Value>,
+    ) -> io::Result<Batch> {
+        let now = Utc::now().timestamp();
+        let batch = Batch {
+            id: format!("batch_{}", Uuid::new_v4().simple()),
+            client: client.to_string(),
+            endpoint: endpoint.to_string(),
+            input_file_id: input_file_id.to_string(),
+            completion_window: COMPLETION_WINDOW.to_string(),
+            status: BatchStatus::Validating,
+            errors: None,
+            output_file_id: None,
+            error_file_id: None,
+            created_at: now,
+            in_progress_at: None,
+            expires_at: now + COMPLETION_WINDOW_SECS,
+            finalizing_at: None,
+            completed_at: None,
+            failed_at: None,
+            expired_at: None,
+            cancelling_at: None,
+            cancelled_at: None,
+            request_counts: BatchRequestCounts::default(),
+            metadata,
+        };
+        self.save(&batch)?;
+        self.batches
+            .lock()
+            .unwrap()
+            .insert(batch.id.clone(), batch.clone());
+        Ok(batch)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Batch> {
+        self.batches.lock().unwrap().get(id).cloned()
+    }
+
+    /// A client's batches, most recently created first.
+    pub fn list(&self, client: &str) -> Vec<Batch> {
+        let mut batches: Vec<Batch> = self
+            .batches
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|b| b.client == client)
+            .cloned()
+            .collect();
+        batches.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        batches
+    }
+
+    /// Batches the runner has not finished, e.g. because the proxy restarted.
+    pub fn unfinished(&self) -> Vec<Batch> {
+        self.batches
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|b| {
+                matches!(
+                    b.status,
+                    BatchStatus::Validating
+                        | BatchStatus::InProgress
+                        | BatchStatus::Finalizing
+                        | BatchStatus::Cancelling
+                )
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// `custom_id`s that already have an output or error line.
+    pub fn completed_ids(&self, id: &str) -> HashSet<String> {
+        [self.output_path(id), self.error_path(id)]
+            .iter()
+            .filter_map(|path| File::open(path).ok())
+            .flat_map(|file| BufReader::new(file).lines().map_while(Result::ok))
+            .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+            .filter_map(|line| line.get("custom_id")?.as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Appends a request's output line, to the error file unless it succeeded.
+    pub fn record_result(&self, id: &str, line: &Value, succeeded: bool) {
+        // Held while appending so lines of concurrent requests never interleave
+        let mut batches = self.batches.lock().unwrap();
+        let Some(batch) = batches.get_mut(id) else {
+            return;
+        };
+        let path = if succeeded {
+            self.output_path(id)
+        } else {
+            self.error_path(id)
+        };
+        let appended = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = appended {
+            error!(error = %e, batch = id, "Failed to append batch output");
+            return;
+        }
+
+        if succeeded {
+            batch.request_counts.completed += 1;
+        } else {
+            batch.request_counts.failed += 1;
+        }
+        if let Err(e) = self.save(batch) {
+            error!(error = %e, batch = id, "Failed to save batch state");
+        }
+    }
+
+    pub fn update(&self, id: &str, change: impl FnOnce(&mut Batch)) -> Option<Batch> {
+        let mut batches = self.batches.lock().unwrap();
+        let batch = batches.get_mut(id)?;
+        change(batch);
+        if let Err(e) = self.save(batch) {
+            error!(error = %e, batch = id, "Failed to save batch state");
+        }
+        Some(batch.clone())
+    }
+
+    pub fn output_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.output.jsonl", id))
+    }
+
+    pub fn error_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.errors.jsonl", id))
+    }
+
+    /// Writes the state file through a temporary file so a crash never leaves it truncated.
+    fn save(&self, batch: &Batch) -> io::Result<()> {
+        let path = self.dir.join(format!("{}.json", batch.id));
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(batch)?)?;
+        fs::rename(&tmp, &path)
+    }
+}
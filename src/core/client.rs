@@ -1,71 +1,586 @@
 use bytes::Bytes;
-use futures::Stream;
-use reqwest::{Client, Response};
+use futures::{Stream, StreamExt};
+use reqwest::{Client, RequestBuilder, Response};
+use std::borrow::Cow;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
 
-use crate::core::Config;
+use crate::api::transformers::{
+    anthropic_to_openai, gemini_to_openai, openai_to_anthropic, openai_to_gemini,
+};
+use crate::common::sse;
+use crate::core::config::{Config, ProviderConfig, ProviderKind, ProviderProfile, RetryConfig};
+use crate::core::keys::{KeyLease, KeyPool};
+use crate::core::profile;
+use crate::core::retry::RetryPolicy;
+use crate::core::router::ResolvedRoute;
 
+/// Response header naming the provider that finally served a request.
+pub const UPSTREAM_HEADER: &str = "x-proxy-upstream";
+
+/// `anthropic-version` sent to `kind: anthropic` providers unless their `headers` set one.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Body of a request sent down a fallback chain.
+pub enum RequestBody {
+    /// Re-sent to each target with its model swapped in.
+    Json(serde_json::Value),
+    /// Any other content type (multipart uploads, audio), forwarded byte for byte.
+    Raw { content_type: String, bytes: Bytes },
+}
+
+/// Pool of upstream clients, one per configured provider.
 #[derive(Clone)]
 pub struct OpenAiClient {
+    providers: Arc<Vec<ProviderClient>>,
+}
+
+/// An upstream response together with the provider and API key that produced it.
+///
+/// Bodies are always handed out in OpenAI chat format, translated from the provider's own
+/// protocol where needed. The key stays checked out of its pool until the body has been consumed.
+pub struct UpstreamResponse {
+    provider: String,
+    kind: ProviderKind,
+    model: String,
+    response: Response,
+    lease: KeyLease,
+    reasoning_signatures: bool,
+}
+
+impl UpstreamResponse {
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// Model name that was sent to the provider.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn status(&self) -> reqwest::StatusCode {
+        self.response.status()
+    }
+
+    /// Keeps Anthropic thinking signatures in the translated body as `reasoning_signature`,
+    /// for routes that convert it back to Anthropic format. Other clients don't get them.
+    pub fn keep_reasoning_signatures(mut self) -> Self {
+        self.reasoning_signatures = true;
+        self
+    }
+
+    pub async fn text(self) -> Result<String, reqwest::Error> {
+        let success = self.status().is_success();
+        let text = self.response.text().await;
+        drop(self.lease);
+        let body = text?;
+        if self.kind == ProviderKind::OpenAi {
+            return Ok(body);
+        }
+        if !success {
+            return Ok(match self.kind {
+                ProviderKind::Gemini => gemini_to_openai::transform_error(&body),
+                _ => anthropic_to_openai::transform_error(&body),
+            });
+        }
+
+        let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&body) else {
+            return Ok(body);
+        };
+        let translated = match self.kind {
+            ProviderKind::Gemini => gemini_to_openai::transform_response(&parsed, &self.model),
+            _ => anthropic_to_openai::transform_response(&parsed, self.reasoning_signatures),
+        };
+        Ok(translated.to_string())
+    }
+
+    pub fn into_stream(self) -> Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>> {
+        let lease = self.lease;
+        match self.kind {
+            ProviderKind::OpenAi => Box::pin(self.response.bytes_stream().map(move |chunk| {
+                let _ = &lease;
+                chunk
+            })),
+            ProviderKind::Anthropic => translate_stream(
+                self.response,
+                lease,
+                anthropic_to_openai::StreamTransformer::new(&self.model, self.reasoning_signatures),
+            ),
+            ProviderKind::Gemini => translate_stream(
+                self.response,
+                lease,
+                gemini_to_openai::StreamTransformer::new(&self.model),
+            ),
+        }
+    }
+}
+
+/// Translates a provider's SSE events into OpenAI chunk lines (`data: ...\n\n`).
+trait StreamTranslator: Send + 'static {
+    fn process_event(&mut self, data: &str) -> Vec<String>;
+    /// Called once the upstream closes; sends whatever the stream has not terminated with yet.
+    fn finish(&mut self) -> Vec<String>;
+}
+
+impl StreamTranslator for anthropic_to_openai::StreamTransformer {
+    fn process_event(&mut self, data: &str) -> Vec<String> {
+        self.process_event(data)
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        self.finish()
+    }
+}
+
+impl StreamTranslator for gemini_to_openai::StreamTransformer {
+    fn process_event(&mut self, data: &str) -> Vec<String> {
+        self.process_event(data)
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        self.finish()
+    }
+}
+
+/// Re-encodes a non-OpenAI SSE body as an OpenAI chunk stream, holding the key until it ends.
+fn translate_stream<T: StreamTranslator>(
+    response: Response,
+    lease: KeyLease,
+    translator: T,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>> {
+    let events = sse::data_lines(Box::pin(response.bytes_stream()));
+    let lines = futures::stream::unfold(Some((events, translator, lease)), |state| async move {
+        let (mut events, mut translator, lease) = state?;
+        match events.next().await {
+            Some(data) => {
+                let lines = translator.process_event(&data);
+                Some((lines, Some((events, translator, lease))))
+            }
+            None => Some((translator.finish(), None)),
+        }
+    });
+    Box::pin(lines.flat_map(|lines| {
+        futures::stream::iter(lines.into_iter().map(|line| Ok(Bytes::from(line))))
+    }))
+}
+
+/// HTTP client bound to a single upstream provider.
+pub struct ProviderClient {
     client: Client,
-    config: Config,
+    config: ProviderConfig,
+    profile: ProviderProfile,
+    keys: Arc<KeyPool>,
+    retry: RetryPolicy,
     chat_completions_url: String,
     models_url: String,
 }
 
 impl OpenAiClient {
-    pub fn new(config: Config) -> Self {
-        let base = config.openai_base_url.trim_end_matches('/');
-        let chat_completions_url = format!("{}/chat/completions", base);
+    pub fn new(config: &Config) -> Self {
+        let providers = config
+            .providers
+            .iter()
+            .map(|provider| {
+                ProviderClient::new(provider.clone(), &config.retry, config.profile(provider))
+            })
+            .collect();
+        Self {
+            providers: Arc::new(providers),
+        }
+    }
+
+    /// Looks up a provider by its configured name.
+    pub fn provider(&self, name: &str) -> Option<&ProviderClient> {
+        self.providers.iter().find(|p| p.name() == name)
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &ProviderClient> {
+        self.providers.iter()
+    }
+
+    /// Sends a chat completion request down the route's fallback chain.
+    ///
+    /// The next provider is tried when sending fails (connect error, timeout, reset) or the
+    /// upstream answers 429/5xx; nothing has reached the client at that point. The last
+    /// provider's response is returned as-is so its error can be forwarded.
+    pub async fn chat_completion(
+        &self,
+        route: &ResolvedRoute,
+        body: serde_json::Value,
+    ) -> Result<UpstreamResponse, reqwest::Error> {
+        self.dispatch(route, RequestBody::Json(body), None).await
+    }
+
+    /// Forwards a request to `path` (relative to each provider's base URL) down the
+    /// route's fallback chain, with the same failover rules as chat completions.
+    pub async fn passthrough(
+        &self,
+        route: &ResolvedRoute,
+        path: &str,
+        body: RequestBody,
+    ) -> Result<UpstreamResponse, reqwest::Error> {
+        self.dispatch(route, body, Some(path)).await
+    }
+
+    /// Walks the fallback chain; `path` selects a passthrough instead of a chat completion.
+    async fn dispatch(
+        &self,
+        route: &ResolvedRoute,
+        mut body: RequestBody,
+        path: Option<&str>,
+    ) -> Result<UpstreamResponse, reqwest::Error> {
+        let mut last_error = None;
+
+        for (attempt, target) in route.targets.iter().enumerate() {
+            let is_last = attempt + 1 == route.targets.len();
+            let provider = self
+                .provider(&target.provider)
+                .expect("routing only refers to configured providers");
+            if let RequestBody::Json(json) = &mut body {
+                json["model"] = serde_json::json!(target.model);
+            }
+
+            let result = match (path, &body) {
+                (Some(path), body) => provider.passthrough(path, body, &target.model).await,
+                (None, RequestBody::Json(json)) => provider.chat_completion(json).await,
+                (None, RequestBody::Raw { .. }) => unreachable!("chat completions are JSON"),
+            };
+            match result {
+                Ok(response) => {
+                    let status = response.status();
+                    if !is_last && is_failover_status(status) {
+                        warn!(
+                            provider = provider.name(),
+                            status = %status,
+                            "Upstream returned retryable status, failing over"
+                        );
+                        continue;
+                    }
+                    if attempt > 0 {
+                        info!(
+                            provider = provider.name(),
+                            model = %target.model,
+                            attempt = attempt + 1,
+                            "Request served by fallback upstream"
+                        );
+                    }
+                    return Ok(response);
+                }
+                Err(e) => {
+                    warn!(provider = provider.name(), error = %e, "Upstream request failed");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        // Every target failed to send; the route always has at least one target
+        Err(last_error.expect("route has no targets"))
+    }
+}
+
+impl ProviderClient {
+    fn new(config: ProviderConfig, default_retry: &RetryConfig, profile: ProviderProfile) -> Self {
+        let base = config.base_url.trim_end_matches('/');
+        let chat_completions_url = match config.kind {
+            ProviderKind::OpenAi => format!("{}/chat/completions", base),
+            ProviderKind::Anthropic => format!("{}/messages", base),
+            // The model and method are appended per request
+            ProviderKind::Gemini => format!("{}/models", base),
+        };
         let models_url = format!("{}/models", base);
+
+        let mut builder = Client::builder();
+        if let Some(secs) = config.connect_timeout_secs {
+            builder = builder.connect_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = config.read_timeout_secs {
+            builder = builder.read_timeout(Duration::from_secs(secs));
+        }
+        let client = builder.build().expect("Failed to build HTTP client");
+        let retry = RetryPolicy::new(
+            config
+                .retry
+                .clone()
+                .unwrap_or_else(|| default_retry.clone()),
+        );
+
+        let keys = Arc::new(KeyPool::new(
+            &config.name,
+            config.keys(),
+            config.key_strategy,
+            Duration::from_secs(config.key_cooldown_secs.unwrap_or(60)),
+        ));
+
         Self {
-            client: Client::new(),
+            client,
             config,
+            profile,
+            keys,
+            retry,
             chat_completions_url,
             models_url,
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.config.base_url
+    }
+
+    pub fn kind(&self) -> ProviderKind {
+        self.config.kind
+    }
+
+    /// Applies a pooled key and the provider's extra headers to a request.
+    fn authorize(&self, request: RequestBuilder, lease: &KeyLease) -> RequestBuilder {
+        let mut request = match self.config.kind {
+            ProviderKind::OpenAi => {
+                request.header("Authorization", format!("Bearer {}", lease.key()))
+            }
+            ProviderKind::Anthropic => {
+                let request = request.header("x-api-key", lease.key());
+                let has_version = self
+                    .config
+                    .headers
+                    .keys()
+                    .any(|name| name.eq_ignore_ascii_case("anthropic-version"));
+                if has_version {
+                    request
+                } else {
+                    request.header("anthropic-version", ANTHROPIC_VERSION)
+                }
+            }
+            ProviderKind::Gemini => request.header("x-goog-api-key", lease.key()),
+        };
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+
+    /// Sends a chat completion request, retrying per the provider's retry policy.
+    ///
+    /// Retries only happen before a response is handed back, so a streamed body is never
+    /// restarted once the client has seen any of it.
     pub async fn chat_completion(
         &self,
-        body: serde_json::Value,
-    ) -> Result<Response, reqwest::Error> {
-        self.client
-            .post(&self.chat_completions_url)
-            .header("Content-Type", "application/json")
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.openai_api_key),
-            )
-            .json(&body)
-            .send()
+        body: &serde_json::Value,
+    ) -> Result<UpstreamResponse, reqwest::Error> {
+        let upstream_body = match self.config.kind {
+            ProviderKind::OpenAi => {
+                let mut shaped = profile::extract_pdfs(&self.profile, body.clone()).await;
+                profile::apply(&self.profile, &mut shaped);
+                shaped
+            }
+            ProviderKind::Anthropic => openai_to_anthropic::transform_request(body),
+            ProviderKind::Gemini => openai_to_gemini::transform_request(body),
+        };
+        let url = self.chat_url(body);
+        let model = body
+            .get("model")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default();
+        self.post(&url, |request| request.json(&upstream_body), model)
             .await
     }
 
-    pub async fn check_connection(&self) -> Result<(), reqwest::Error> {
-        // Try to list models as a connection check
+    /// Forwards a request body unchanged to `{base_url}{path}`, e.g. `/embeddings`, with the
+    /// same retry policy as chat completions. Only meaningful for OpenAI-kind providers.
+    pub async fn passthrough(
+        &self,
+        path: &str,
+        body: &RequestBody,
+        model: &str,
+    ) -> Result<UpstreamResponse, reqwest::Error> {
+        let url = format!("{}{}", self.config.base_url.trim_end_matches('/'), path);
+        match body {
+            RequestBody::Json(json) => self.post(&url, |request| request.json(json), model).await,
+            RequestBody::Raw {
+                content_type,
+                bytes,
+            } => {
+                self.post(
+                    &url,
+                    |request| {
+                        request
+                            .header("Content-Type", content_type.as_str())
+                            .body(bytes.clone())
+                    },
+                    model,
+                )
+                .await
+            }
+        }
+    }
+
+    /// POSTs the body `with_body` attaches, retrying per the provider's retry policy; `model`
+    /// is the model name the request was sent for.
+    async fn post(
+        &self,
+        url: &str,
+        with_body: impl Fn(RequestBuilder) -> RequestBuilder,
+        model: &str,
+    ) -> Result<UpstreamResponse, reqwest::Error> {
+        let max_attempts = self.retry.max_attempts();
+        let mut attempt = 1;
+
+        loop {
+            // Each attempt draws a fresh key so a rejected one is not reused
+            let lease = self.keys.acquire();
+            let result = with_body(self.authorize(self.client.post(url), &lease))
+                .send()
+                .await;
+
+            if let Ok(response) = &result {
+                lease.report(response.status());
+            }
+
+            if attempt >= max_attempts {
+                return result.map(|response| self.wrap(model, response, lease));
+            }
+
+            let delay = match &result {
+                Ok(response) if self.retry.is_retryable(response.status()) => self
+                    .retry
+                    .delay(attempt, Some((response.status(), response.headers()))),
+                Ok(_) => return result.map(|response| self.wrap(model, response, lease)),
+                Err(_) => self.retry.delay(attempt, None),
+            };
+
+            match &result {
+                Ok(response) => warn!(
+                    provider = self.name(),
+                    status = %response.status(),
+                    attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    "Retrying upstream request"
+                ),
+                Err(e) => warn!(
+                    provider = self.name(),
+                    error = %e,
+                    attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    "Retrying upstream request"
+                ),
+            }
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Endpoint for a chat request; Gemini names the model and streaming mode in the path.
+    fn chat_url(&self, body: &serde_json::Value) -> Cow<'_, str> {
+        if self.config.kind != ProviderKind::Gemini {
+            return Cow::Borrowed(&self.chat_completions_url);
+        }
+        let model = body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+        let method = if body.get("stream").and_then(|s| s.as_bool()) == Some(true) {
+            "streamGenerateContent?alt=sse"
+        } else {
+            "generateContent"
+        };
+        Cow::Owned(format!(
+            "{}/{}:{}",
+            self.chat_completions_url, model, method
+        ))
+    }
+
+    fn wrap(&self, model: &str, response: Response, lease: KeyLease) -> UpstreamResponse {
+        UpstreamResponse {
+            provider: self.name().to_string(),
+            kind: self.config.kind,
+            model: model.to_string(),
+            response,
+            lease,
+            reasoning_signatures: false,
+        }
+    }
+
+    /// Fetches the provider's `/models` list; entries are returned as the upstream sent them.
+    ///
+    /// The status is not reported to the key pool: a health probe or model listing that hits
+    /// a 429 should not eject a key from production traffic for the whole cooldown.
+    pub async fn list_models(
+        &self,
+        timeout: Duration,
+    ) -> Result<Vec<serde_json::Value>, ConnectionError> {
+        let lease = self.keys.acquire();
         let response = self
-            .client
-            .get(&self.models_url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.openai_api_key),
-            )
+            .authorize(self.client.get(&self.models_url), &lease)
+            .timeout(timeout)
             .send()
-            .await?;
+            .await
+            .map_err(ConnectionError::Unreachable)?;
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            // Even if it returns 401/404, the API is reachable.
-            // Only return error if we can't reach the server at all.
-            Ok(())
+        let status = response.status();
+        if !status.is_success() {
+            return Err(match status {
+                reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                    ConnectionError::Unauthorized(status)
+                }
+                reqwest::StatusCode::TOO_MANY_REQUESTS => ConnectionError::RateLimited,
+                _ => ConnectionError::Status(status),
+            });
+        }
+
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        if self.config.kind == ProviderKind::Gemini {
+            // Gemini lists `models/<id>` names under `models`
+            return Ok(body
+                .get("models")
+                .and_then(|m| m.as_array())
+                .map(|models| {
+                    models
+                        .iter()
+                        .filter_map(|m| m.get("name").and_then(|n| n.as_str()))
+                        .map(|name| {
+                            serde_json::json!({
+                                "id": name.trim_start_matches("models/"),
+                                "object": "model"
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default());
         }
+        Ok(body
+            .get("data")
+            .and_then(|d| d.as_array())
+            .cloned()
+            .unwrap_or_default())
     }
+}
+
+/// Why an upstream failed its connection check.
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// No HTTP response at all (DNS, connect, TLS, timeout).
+    Unreachable(reqwest::Error),
+    /// The upstream rejected the API key.
+    Unauthorized(reqwest::StatusCode),
+    /// Reachable and authenticated, but currently rate limited.
+    RateLimited,
+    /// Any other non-success status, e.g. a wrong base_url (404) or an outage (5xx).
+    Status(reqwest::StatusCode),
+}
 
-    pub fn stream_response(
-        response: Response,
-    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>> {
-        Box::pin(response.bytes_stream())
+impl std::fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable(e) => write!(f, "unreachable: {}", e),
+            Self::Unauthorized(status) => write!(f, "API key rejected ({})", status),
+            Self::RateLimited => write!(f, "rate limited (429)"),
+            Self::Status(status) => write!(f, "unexpected status {}", status),
+        }
     }
 }
+
+fn is_failover_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
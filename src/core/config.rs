@@ -1,18 +1,402 @@
 use directories::UserDirs;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::core::keys::KeyStrategy;
+
+/// Name given to the provider built from the legacy top-level `openai` section.
+pub const LEGACY_PROVIDER_NAME: &str = "openai";
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
-    pub openai_api_key: String,
-    pub openai_base_url: String,
+    pub providers: Vec<ProviderConfig>,
+    /// Named request-shaping profiles that providers refer to with `profile`.
+    pub profiles: HashMap<String, ProviderProfile>,
+    /// Default retry policy for providers that don't declare their own.
+    pub retry: RetryConfig,
+    pub routing: RoutingConfig,
+    pub auth: AuthConfig,
+    pub pricing: PricingConfig,
+    pub health: HealthConfig,
+    pub tokenizer: TokenizerConfig,
+    /// Extra `/v1/...` POST paths forwarded verbatim to OpenAI-kind providers, e.g. `/v1/rerank`.
+    pub passthrough: Vec<String>,
+    pub batches: BatchConfig,
+    /// Secret for the HMAC signatures on thinking blocks; random per start when omitted.
+    pub thinking_signing_key: Option<String>,
+    /// Append-only JSONL file recording token usage per request.
+    pub usage_log: PathBuf,
     pub port: u16,
 }
 
+/// A single named upstream, reached over the API its `kind` names.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProviderConfig {
+    pub name: String,
+    #[serde(default)]
+    pub kind: ProviderKind,
+    pub base_url: String,
+    /// Single key; merged with `api_keys` when both are given.
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    #[serde(default)]
+    pub key_strategy: KeyStrategy,
+    /// How long a key that returned 401/429 stays out of rotation (default 60).
+    pub key_cooldown_secs: Option<u64>,
+    /// Extra headers sent with every upstream request (e.g. `HTTP-Referer` for OpenRouter).
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Maximum time to establish a TCP/TLS connection.
+    pub connect_timeout_secs: Option<u64>,
+    /// Maximum time to wait between reads; applies to each chunk of a streamed response.
+    pub read_timeout_secs: Option<u64>,
+    /// Overrides the top-level `retry` policy for this provider.
+    pub retry: Option<RetryConfig>,
+    /// Name of an entry in `profiles` describing what this upstream accepts.
+    pub profile: Option<String>,
+}
+
+/// Wire protocol spoken by an upstream. Requests are always built in OpenAI chat format
+/// and translated at the client for other kinds.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI chat completions (`/chat/completions`), including compatible servers.
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Anthropic Messages API (`/messages`), authenticated with `x-api-key`.
+    Anthropic,
+    /// Google Gemini REST API (`/models/{model}:generateContent`), authenticated with `x-goog-api-key`.
+    Gemini,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    /// Relative share of traffic under the `weighted` strategy.
+    #[serde(default = "default_key_weight")]
+    pub weight: u32,
+}
+
+fn default_key_weight() -> u32 {
+    1
+}
+
+impl ProviderConfig {
+    /// All keys configured for this provider, `api_key` first.
+    pub fn keys(&self) -> Vec<ApiKeyConfig> {
+        self.api_key
+            .iter()
+            .map(|key| ApiKeyConfig {
+                key: key.clone(),
+                weight: default_key_weight(),
+            })
+            .chain(self.api_keys.iter().cloned())
+            .collect()
+    }
+}
+
+/// How requests are shaped for an OpenAI-kind upstream that is not quite OpenAI.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProviderProfile {
+    /// Field the upstream reads extended thinking / reasoning effort from.
+    pub thinking: ThinkingStyle,
+    /// Sends verified thinking from earlier assistant turns back as `reasoning_content`
+    /// (DeepSeek, Qwen); it is stripped from history otherwise.
+    pub replay_reasoning: bool,
+    /// Where images (and PDFs) returned by tools are put.
+    pub tool_images: ToolImages,
+    /// How PDF documents are sent.
+    pub pdf: PdfHandling,
+    /// Put in front of tool results the client flagged with `is_error` (default `Error: `).
+    pub tool_error_prefix: Option<String>,
+    /// Thinking budgets mapped to `low`/`medium`/`high` effort per upstream model;
+    /// the first matching entry wins.
+    pub effort_thresholds: Vec<EffortThresholdConfig>,
+    /// String values swapped per field before anything else, e.g.
+    /// `tool_choice: { required: auto }`.
+    pub replace: HashMap<String, HashMap<String, String>>,
+    /// Fields removed from the request; nested ones as dotted paths (`stream_options`,
+    /// `chat_template_kwargs.enable_thinking`).
+    pub drop: Vec<String>,
+    /// Fields moved to another name, e.g. `max_tokens: max_completion_tokens`.
+    pub rename: HashMap<String, String>,
+    /// Fields set on every request, overriding what the client sent.
+    pub inject: HashMap<String, serde_json::Value>,
+    /// Replaces image parts with a text placeholder for upstreams without vision.
+    pub drop_images: bool,
+}
+
+/// Where a provider expects to be told about thinking.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkingStyle {
+    /// OpenAI's `reasoning_effort: low|medium|high`.
+    #[default]
+    ReasoningEffort,
+    /// OpenRouter's `reasoning` object (`max_tokens`, `effort` or `enabled`).
+    Reasoning,
+    /// `chat_template_kwargs.enable_thinking`, read by vLLM/SGLang chat templates (Qwen, DeepSeek).
+    ChatTemplateKwargs,
+    /// Strip thinking fields entirely.
+    #[serde(rename = "none")]
+    Omit,
+}
+
+/// Where an OpenAI-kind provider receives images that a tool returned.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolImages {
+    /// A user message after the tool results, since OpenAI tool messages only take text.
+    #[default]
+    UserMessage,
+    /// `image_url` parts in the tool message itself, for upstreams that accept them.
+    ToolContent,
+}
+
+/// How an OpenAI-kind provider receives PDF documents.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfHandling {
+    /// OpenAI `file` parts carrying the base64 PDF.
+    #[default]
+    File,
+    /// Text extracted locally, for upstreams without file input.
+    Text,
+}
+
+/// Budgets up to `low` tokens map to `low` effort, up to `medium` to `medium`, larger to `high`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EffortThresholdConfig {
+    /// Upstream model name or glob.
+    #[serde(rename = "match")]
+    pub pattern: String,
+    pub low: u64,
+    pub medium: u64,
+}
+
+/// Retry policy applied to a single provider before failing over to the next one.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Total attempts including the first one; `1` disables retries.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: bool,
+    pub retryable_status: Vec<u16>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter: true,
+            retryable_status: vec![429, 500, 502, 503, 504],
+        }
+    }
+}
+
+/// Model-name routing table. Exact names win over patterns, patterns are tried in order,
+/// and anything unmatched goes to `default` (or the first provider).
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RoutingConfig {
+    pub default: Option<String>,
+    /// Fallback chain for models that only hit the default route.
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackConfig>,
+    #[serde(default)]
+    pub models: Vec<ModelRouteConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelRouteConfig {
+    /// Exact model name, or a glob using `*` and `?` (e.g. `claude-3-5-sonnet*`).
+    #[serde(rename = "match")]
+    pub pattern: String,
+    pub provider: String,
+    /// Model name sent upstream; the requested name is kept when omitted.
+    pub model: Option<String>,
+    /// Providers tried in order when the primary one fails before responding.
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FallbackConfig {
+    pub provider: String,
+    pub model: Option<String>,
+}
+
+/// Virtual API keys clients use to call the proxy. Authentication is off when empty.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub keys: Vec<VirtualKeyConfig>,
+    /// Bearer token for the `/admin/*` endpoints. Without it they are only reachable
+    /// while client authentication is off.
+    pub admin_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VirtualKeyConfig {
+    pub name: String,
+    pub key: String,
+    /// Model names or globs this key may request; empty allows every model.
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Limits shared by every request made with this key.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Additional limits for models matching a name or glob, tracked per key and per
+    /// `routing.models` entry the request matched; models on the default route share one.
+    #[serde(default)]
+    pub model_rate_limits: Vec<ModelRateLimitConfig>,
+    /// Input plus output tokens allowed per calendar month (UTC).
+    pub monthly_token_budget: Option<u64>,
+    /// Estimated cost in USD, per `pricing`, allowed per calendar month (UTC).
+    pub monthly_cost_budget: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelRateLimitConfig {
+    #[serde(rename = "match")]
+    pub pattern: String,
+    #[serde(flatten)]
+    pub limit: RateLimitConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Background probing of upstreams for `/health/ready`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Seconds between probes of each provider's `/models`.
+    pub probe_interval_secs: u64,
+    pub probe_timeout_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval_secs: 30,
+            probe_timeout_secs: 10,
+        }
+    }
+}
+
+/// Local tokenizer used by `/v1/messages/count_tokens`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TokenizerConfig {
+    /// tiktoken rank file, e.g. `cl100k_base.tiktoken`; tokens are estimated from
+    /// character counts without one.
+    pub bpe_file: Option<PathBuf>,
+    /// `cl100k_base`, `o200k_base` or a custom split regex; guessed from the file name by default.
+    pub pattern: Option<String>,
+}
+
+/// Local execution of batches: Anthropic Message Batches (`/v1/messages/batches`) and
+/// OpenAI batches (`/v1/batches`) with their `/v1/files`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Requests of one batch in flight at the same time.
+    pub concurrency: usize,
+    /// Retries of a single batch request, OpenAI or Anthropic, that failed with a retryable
+    /// status.
+    pub retry: RetryConfig,
+    /// Where submitted batches and their results are kept; `batches/` next to config.yaml
+    /// by default.
+    pub dir: Option<PathBuf>,
+    /// Where `/v1/files` uploads and batch output files are kept; `files/` next to
+    /// config.yaml by default.
+    pub files_dir: Option<PathBuf>,
+}
+
+impl BatchConfig {
+    pub fn batch_dir(&self) -> PathBuf {
+        self.dir
+            .clone()
+            .unwrap_or_else(|| Config::get_data_dir().join("batches"))
+    }
+
+    pub fn files_dir(&self) -> PathBuf {
+        self.files_dir
+            .clone()
+            .unwrap_or_else(|| Config::get_data_dir().join("files"))
+    }
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            retry: RetryConfig {
+                max_attempts: 3,
+                ..Default::default()
+            },
+            dir: None,
+            files_dir: None,
+        }
+    }
+}
+
+/// Prices used to estimate the cost of each request.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PricingConfig {
+    /// Adds `x-proxy-cost-usd` to non-streaming responses whose model has a price.
+    #[serde(default)]
+    pub cost_header: bool,
+    #[serde(default)]
+    pub models: Vec<ModelPriceConfig>,
+}
+
+/// USD per million tokens for upstream models matching a name or glob.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelPriceConfig {
+    /// Upstream model name or glob, matched in order.
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Restricts the price to one provider; applies to all providers when omitted.
+    pub provider: Option<String>,
+    pub input: f64,
+    pub output: f64,
+    /// Price of cached prompt tokens; billed as regular input when omitted.
+    pub cached_input: Option<f64>,
+}
+
 #[derive(Deserialize)]
 struct ConfigFile {
     openai: Option<OpenAiConfig>,
+    providers: Option<Vec<ProviderConfig>>,
+    profiles: Option<HashMap<String, ProviderProfile>>,
+    routing: Option<RoutingConfig>,
+    retry: Option<RetryConfig>,
+    auth: Option<AuthConfig>,
+    pricing: Option<PricingConfig>,
+    health: Option<HealthConfig>,
+    tokenizer: Option<TokenizerConfig>,
+    passthrough: Option<Vec<String>>,
+    batches: Option<BatchConfig>,
+    thinking_signing_key: Option<String>,
+    usage_log: Option<PathBuf>,
     port: Option<u16>,
 }
 
@@ -38,13 +422,33 @@ impl Config {
         config_dir.join("config.yaml")
     }
 
+    /// Directory holding config.yaml and the proxy's local data files.
+    pub fn get_data_dir() -> PathBuf {
+        let config_path = Self::get_config_path();
+        config_path
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default()
+    }
+
+    /// The profile a provider refers to, or the default (plain OpenAI) one.
+    pub fn profile(&self, provider: &ProviderConfig) -> ProviderProfile {
+        provider
+            .profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
     pub fn load() -> Self {
         let config_path = Self::get_config_path();
 
         if !config_path.exists() {
-            let default_yaml = r#"openai:
-  api_key: "your-api-key-here"
-  base_url: "https://integrate.api.nvidia.com/v1"
+            let default_yaml = r#"providers:
+  - name: "nvidia"
+    api_key: "your-api-key-here"
+    base_url: "https://integrate.api.nvidia.com/v1"
 port: 3000
 "#;
             fs::write(&config_path, default_yaml).expect("Failed to write default config.yaml");
@@ -62,17 +466,128 @@ port: 3000
         let file_config: ConfigFile = serde_yaml::from_str(&content)
             .expect("Failed to parse config.yaml. Please ensure it has the correct format.");
 
-        let openai = file_config
-            .openai
-            .expect("config.yaml must contain an 'openai' section.");
+        let mut providers = Vec::new();
+
+        // The legacy single-upstream section is kept working as a provider named "openai"
+        if let Some(openai) = file_config.openai {
+            providers.push(ProviderConfig {
+                name: LEGACY_PROVIDER_NAME.to_string(),
+                kind: ProviderKind::OpenAi,
+                api_key: Some(
+                    openai
+                        .api_key
+                        .expect("openai.api_key is required in config.yaml"),
+                ),
+                api_keys: Vec::new(),
+                key_strategy: KeyStrategy::default(),
+                key_cooldown_secs: None,
+                base_url: openai
+                    .base_url
+                    .expect("openai.base_url is required in config.yaml"),
+                headers: HashMap::new(),
+                connect_timeout_secs: None,
+                read_timeout_secs: None,
+                retry: None,
+                profile: None,
+            });
+        }
+        providers.extend(file_config.providers.unwrap_or_default());
+
+        if providers.is_empty() {
+            panic!("config.yaml must contain a 'providers' list or an 'openai' section.");
+        }
+
+        let mut seen = HashSet::new();
+        for provider in &providers {
+            if !seen.insert(provider.name.as_str()) {
+                panic!("Duplicate provider name '{}' in config.yaml", provider.name);
+            }
+            if provider.keys().is_empty() {
+                panic!(
+                    "Provider '{}' needs 'api_key' or 'api_keys' in config.yaml",
+                    provider.name
+                );
+            }
+        }
+
+        let profiles = file_config.profiles.unwrap_or_default();
+        for provider in &providers {
+            if let Some(profile) = &provider.profile {
+                if !profiles.contains_key(profile) {
+                    panic!(
+                        "Provider '{}' refers to unknown profile '{}' in config.yaml",
+                        provider.name, profile
+                    );
+                }
+            }
+        }
+
+        let routing = file_config.routing.unwrap_or_default();
+        let referenced = routing
+            .default
+            .iter()
+            .chain(routing.fallbacks.iter().map(|f| &f.provider))
+            .chain(routing.models.iter().flat_map(|r| {
+                std::iter::once(&r.provider).chain(r.fallbacks.iter().map(|f| &f.provider))
+            }));
+        for name in referenced {
+            if !seen.contains(name.as_str()) {
+                panic!(
+                    "routing refers to unknown provider '{}' in config.yaml",
+                    name
+                );
+            }
+        }
+
+        let auth = file_config.auth.unwrap_or_default();
+        let mut key_names = HashSet::new();
+        for key in &auth.keys {
+            if !key_names.insert(key.name.as_str()) {
+                panic!("Duplicate auth key name '{}' in config.yaml", key.name);
+            }
+        }
+
+        let pricing = file_config.pricing.unwrap_or_default();
+        for price in &pricing.models {
+            if let Some(name) = &price.provider {
+                if !seen.contains(name.as_str()) {
+                    panic!(
+                        "pricing refers to unknown provider '{}' in config.yaml",
+                        name
+                    );
+                }
+            }
+        }
+
+        let passthrough = file_config.passthrough.unwrap_or_default();
+        let mut paths = HashSet::new();
+        for path in &passthrough {
+            if !path.starts_with("/v1/") {
+                panic!(
+                    "passthrough path '{}' in config.yaml must start with /v1/",
+                    path
+                );
+            }
+            if !paths.insert(path.as_str()) {
+                panic!("Duplicate passthrough path '{}' in config.yaml", path);
+            }
+        }
 
         Self {
-            openai_api_key: openai
-                .api_key
-                .expect("openai.api_key is required in config.yaml"),
-            openai_base_url: openai
-                .base_url
-                .expect("openai.base_url is required in config.yaml"),
+            providers,
+            profiles,
+            retry: file_config.retry.unwrap_or_default(),
+            routing,
+            auth,
+            pricing,
+            health: file_config.health.unwrap_or_default(),
+            tokenizer: file_config.tokenizer.unwrap_or_default(),
+            passthrough,
+            batches: file_config.batches.unwrap_or_default(),
+            thinking_signing_key: file_config.thinking_signing_key,
+            usage_log: file_config
+                .usage_log
+                .unwrap_or_else(|| Self::get_data_dir().join("usage.jsonl")),
             port: file_config.port.unwrap_or(3000),
         }
     }
@@ -0,0 +1,140 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Metadata of one stored file, kept in `{id}.json` next to its content.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredFile {
+    pub id: String,
+    /// Virtual key name that owns the file; only it can see the file.
+    pub client: String,
+    pub filename: String,
+    pub purpose: String,
+    pub bytes: u64,
+    pub created_at: i64,
+}
+
+/// Files uploaded through `/v1/files` and written by batches, kept on disk.
+pub struct FileStore {
+    dir: PathBuf,
+    files: Mutex<HashMap<String, StoredFile>>,
+}
+
+impl FileStore {
+    pub fn open(dir: &Path) -> Self {
+        if let Err(e) = fs::create_dir_all(dir) {
+            error!(error = %e, dir = %dir.display(), "Cannot create file directory");
+        }
+
+        let mut files = HashMap::new();
+        if let Ok(entries) = fs::read_dir(dir) {
+            for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                match fs::read(&path)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<StoredFile>(&bytes).ok())
+                {
+                    Some(file) => {
+                        files.insert(file.id.clone(), file);
+                    }
+                    None => warn!(path = %path.display(), "Skipped unreadable file metadata"),
+                }
+            }
+            info!(files = files.len(), dir = %dir.display(), "Loaded stored files");
+        }
+
+        Self {
+            dir: dir.to_path_buf(),
+            files: Mutex::new(files),
+        }
+    }
+
+    pub fn create(
+        &self,
+        client: &str,
+        filename: &str,
+        purpose: &str,
+        content: &[u8],
+    ) -> io::Result<StoredFile> {
+        let file = self.metadata(client, filename, purpose, content.len() as u64);
+        fs::write(self.content_path(&file.id), content)?;
+        self.insert(file)
+    }
+
+    /// Moves an existing file (e.g. a finished batch's output) into the store.
+    pub fn import(
+        &self,
+        client: &str,
+        filename: &str,
+        purpose: &str,
+        source: &Path,
+    ) -> io::Result<StoredFile> {
+        let bytes = fs::metadata(source)?.len();
+        let file = self.metadata(client, filename, purpose, bytes);
+        fs::rename(source, self.content_path(&file.id))?;
+        self.insert(file)
+    }
+
+    pub fn get(&self, id: &str) -> Option<StoredFile> {
+        self.files.lock().unwrap().get(id).cloned()
+    }
+
+    /// A client's files, most recently created first.
+    pub fn list(&self, client: &str) -> Vec<StoredFile> {
+        let mut files: Vec<StoredFile> = self
+            .files
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|f| f.client == client)
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        files
+    }
+
+    pub fn content(&self, id: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.content_path(id))
+    }
+
+    pub fn delete(&self, id: &str) {
+        self.files.lock().unwrap().remove(id);
+        let _ = fs::remove_file(self.content_path(id));
+        let _ = fs::remove_file(self.dir.join(format!("{}.json", id)));
+    }
+
+    fn metadata(&self, client: &str, filename: &str, purpose: &str, bytes: u64) -> StoredFile {
+        StoredFile {
+            id: format!("file-{}", Uuid::new_v4().simple()),
+            client: client.to_string(),
+            filename: filename.to_string(),
+            purpose: purpose.to_string(),
+            bytes,
+            created_at: Utc::now().timestamp(),
+        }
+    }
+
+    fn insert(&self, file: StoredFile) -> io::Result<StoredFile> {
+        fs::write(
+            self.dir.join(format!("{}.json", file.id)),
+            serde_json::to_vec_pretty(&file)?,
+        )?;
+        self.files
+            .lock()
+            .unwrap()
+            .insert(file.id.clone(), file.clone());
+        Ok(file)
+    }
+
+    fn content_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.data", id))
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::core::client::ConnectionError;
+use crate::core::config::HealthConfig;
+use crate::core::OpenAiClient;
+
+/// Outcome of the latest probe of one upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamState {
+    /// Not probed yet.
+    Unknown,
+    Healthy,
+    /// Reachable and authenticated, but the key is currently rate limited.
+    RateLimited,
+    /// The upstream rejected the API key (401/403).
+    Unauthorized,
+    /// No HTTP response (DNS, connect, TLS, timeout).
+    Unreachable,
+    /// Any other non-success status.
+    Unhealthy,
+}
+
+impl UpstreamState {
+    /// Whether requests routed to the upstream can be expected to succeed.
+    pub fn is_usable(self) -> bool {
+        matches!(self, Self::Healthy | Self::RateLimited)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct UpstreamHealth {
+    pub name: String,
+    pub state: UpstreamState,
+    pub error: Option<String>,
+    pub latency_ms: Option<u64>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// Periodically probes every provider's `/models` and keeps the latest result for readiness,
+/// along with the model list for `/v1/models`.
+pub struct HealthMonitor {
+    client: OpenAiClient,
+    interval: Duration,
+    timeout: Duration,
+    upstreams: Mutex<HashMap<String, UpstreamHealth>>,
+    /// Last list each provider returned; kept through failed probes so a passing 429 does
+    /// not hide a provider's models.
+    models: Mutex<HashMap<String, Vec<Value>>>,
+}
+
+impl HealthMonitor {
+    pub fn new(client: OpenAiClient, config: &HealthConfig) -> Self {
+        let upstreams = client
+            .providers()
+            .map(|p| {
+                (
+                    p.name().to_string(),
+                    UpstreamHealth {
+                        name: p.name().to_string(),
+                        state: UpstreamState::Unknown,
+                        error: None,
+                        latency_ms: None,
+                        checked_at: None,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            client,
+            interval: Duration::from_secs(config.probe_interval_secs.max(1)),
+            timeout: Duration::from_secs(config.probe_timeout_secs.max(1)),
+            upstreams: Mutex::new(upstreams),
+            models: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Probes every provider once, concurrently, and stores the results.
+    pub async fn probe_all(&self) -> Vec<UpstreamHealth> {
+        let probes = self.client.providers().map(|provider| async move {
+            let started = Instant::now();
+            let result = provider.list_models(self.timeout).await;
+            (provider.name(), result, started.elapsed())
+        });
+
+        for (name, result, latency) in futures::future::join_all(probes).await {
+            self.record(name, result, latency);
+        }
+        self.snapshot()
+    }
+
+    fn record(&self, name: &str, result: Result<Vec<Value>, ConnectionError>, latency: Duration) {
+        let state = match &result {
+            Ok(_) => UpstreamState::Healthy,
+            Err(ConnectionError::Unreachable(_)) => UpstreamState::Unreachable,
+            Err(ConnectionError::Unauthorized(_)) => UpstreamState::Unauthorized,
+            Err(ConnectionError::RateLimited) => UpstreamState::RateLimited,
+            Err(ConnectionError::Status(_)) => UpstreamState::Unhealthy,
+        };
+        let error = match result {
+            Ok(models) => {
+                self.models.lock().unwrap().insert(name.to_string(), models);
+                None
+            }
+            Err(e) => Some(e.to_string()),
+        };
+
+        let mut upstreams = self.upstreams.lock().unwrap();
+        let Some(entry) = upstreams.get_mut(name) else {
+            return;
+        };
+        // The first result is reported by the startup check
+        if entry.state != state && entry.state != UpstreamState::Unknown {
+            if state.is_usable() {
+                info!(provider = name, state = ?state, "Upstream health changed");
+            } else {
+                warn!(provider = name, state = ?state, error = ?error, "Upstream health changed");
+            }
+        }
+        entry.state = state;
+        entry.error = error;
+        entry.latency_ms = Some(latency.as_millis() as u64);
+        entry.checked_at = Some(Utc::now());
+    }
+
+    /// Latest result per provider, in configuration order.
+    pub fn snapshot(&self) -> Vec<UpstreamHealth> {
+        let upstreams = self.upstreams.lock().unwrap();
+        self.client
+            .providers()
+            .filter_map(|p| upstreams.get(p.name()).cloned())
+            .collect()
+    }
+
+    /// Each provider's models as of its last successful probe, in configuration order.
+    pub fn models(&self) -> Vec<(String, Vec<Value>)> {
+        let models = self.models.lock().unwrap();
+        self.client
+            .providers()
+            .filter_map(|p| Some((p.name().to_string(), models.get(p.name())?.clone())))
+            .collect()
+    }
+
+    /// Ready while at least one upstream is usable.
+    pub fn is_ready(&self) -> bool {
+        self.upstreams
+            .lock()
+            .unwrap()
+            .values()
+            .any(|u| u.state.is_usable())
+    }
+
+    /// Re-probes all upstreams every interval until the process exits.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            // The first tick fires immediately; startup already probed once
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                self.probe_all().await;
+            }
+        });
+    }
+}
@@ -0,0 +1,239 @@
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::core::config::ApiKeyConfig;
+
+/// How the next key is picked from a provider's pool.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStrategy {
+    #[default]
+    RoundRobin,
+    Weighted,
+    LeastInFlight,
+}
+
+struct KeySlot {
+    key: String,
+    weight: u32,
+    in_flight: AtomicUsize,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl KeySlot {
+    fn is_available(&self, now: Instant) -> bool {
+        match *self.ejected_until.lock().unwrap() {
+            Some(until) => until <= now,
+            None => true,
+        }
+    }
+
+    fn ejected_until(&self) -> Option<Instant> {
+        *self.ejected_until.lock().unwrap()
+    }
+}
+
+/// Pool of API keys for one upstream provider.
+///
+/// Keys that answer 401 or 429 are ejected for `cooldown` and re-admitted afterwards.
+/// When every key is ejected, the one that comes back soonest is used anyway so requests
+/// keep flowing and the upstream's error reaches the client.
+pub struct KeyPool {
+    provider: String,
+    slots: Vec<KeySlot>,
+    strategy: KeyStrategy,
+    cooldown: Duration,
+    cursor: AtomicUsize,
+}
+
+/// A key checked out of the pool; counts as in flight until dropped.
+pub struct KeyLease {
+    pool: Arc<KeyPool>,
+    index: usize,
+}
+
+impl KeyLease {
+    pub fn key(&self) -> &str {
+        &self.pool.slots[self.index].key
+    }
+
+    /// Feeds an upstream status back into the pool, ejecting the key on 401/429.
+    pub fn report(&self, status: reqwest::StatusCode) {
+        if status == reqwest::StatusCode::UNAUTHORIZED
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            let slot = &self.pool.slots[self.index];
+            *slot.ejected_until.lock().unwrap() = Some(Instant::now() + self.pool.cooldown);
+            warn!(
+                provider = %self.pool.provider,
+                key_index = self.index,
+                status = %status,
+                cooldown_secs = self.pool.cooldown.as_secs(),
+                "Ejecting API key from pool"
+            );
+        }
+    }
+}
+
+impl Drop for KeyLease {
+    fn drop(&mut self) {
+        self.pool.slots[self.index]
+            .in_flight
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl KeyPool {
+    pub fn new(
+        provider: &str,
+        keys: Vec<ApiKeyConfig>,
+        strategy: KeyStrategy,
+        cooldown: Duration,
+    ) -> Self {
+        let slots = keys
+            .into_iter()
+            .map(|k| KeySlot {
+                key: k.key,
+                weight: k.weight.max(1),
+                in_flight: AtomicUsize::new(0),
+                ejected_until: Mutex::new(None),
+            })
+            .collect();
+        Self {
+            provider: provider.to_string(),
+            slots,
+            strategy,
+            cooldown,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn acquire(self: &Arc<Self>) -> KeyLease {
+        let index = self.pick();
+        self.slots[index].in_flight.fetch_add(1, Ordering::Relaxed);
+        KeyLease {
+            pool: Arc::clone(self),
+            index,
+        }
+    }
+
+    fn pick(&self) -> usize {
+        let now = Instant::now();
+        let available: Vec<usize> = (0..self.slots.len())
+            .filter(|&i| self.slots[i].is_available(now))
+            .collect();
+
+        if available.is_empty() {
+            // All keys are cooling down; use the one that is re-admitted first
+            return (0..self.slots.len())
+                .min_by_key(|&i| self.slots[i].ejected_until())
+                .unwrap_or(0);
+        }
+
+        match self.strategy {
+            KeyStrategy::RoundRobin => {
+                let n = self.cursor.fetch_add(1, Ordering::Relaxed);
+                available[n % available.len()]
+            }
+            KeyStrategy::Weighted => {
+                let total: usize = available
+                    .iter()
+                    .map(|&i| self.slots[i].weight as usize)
+                    .sum();
+                let mut n = self.cursor.fetch_add(1, Ordering::Relaxed) % total;
+                for &i in &available {
+                    let weight = self.slots[i].weight as usize;
+                    if n < weight {
+                        return i;
+                    }
+                    n -= weight;
+                }
+                available[0]
+            }
+            KeyStrategy::LeastInFlight => {
+                // Rotate the starting point so ties don't always land on the first key
+                let offset = self.cursor.fetch_add(1, Ordering::Relaxed);
+                (0..available.len())
+                    .map(|k| available[(k + offset) % available.len()])
+                    .min_by_key(|&i| self.slots[i].in_flight.load(Ordering::Relaxed))
+                    .unwrap_or(available[0])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(weights: &[u32], strategy: KeyStrategy) -> Arc<KeyPool> {
+        let keys = weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| ApiKeyConfig {
+                key: format!("k{}", i),
+                weight,
+            })
+            .collect();
+        Arc::new(KeyPool::new(
+            "test",
+            keys,
+            strategy,
+            Duration::from_secs(60),
+        ))
+    }
+
+    fn picks(pool: &KeyPool, n: usize) -> Vec<usize> {
+        (0..n).map(|_| pool.pick()).collect()
+    }
+
+    #[test]
+    fn round_robin_cycles_through_keys() {
+        let pool = pool(&[1, 1, 1], KeyStrategy::RoundRobin);
+        assert_eq!(picks(&pool, 6), vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn weighted_follows_weights() {
+        let pool = pool(&[3, 1], KeyStrategy::Weighted);
+        assert_eq!(picks(&pool, 8), vec![0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn least_in_flight_avoids_busy_keys() {
+        let pool = pool(&[1, 1, 1], KeyStrategy::LeastInFlight);
+        let first = pool.acquire();
+        let second = pool.acquire();
+        assert_ne!(first.key(), second.key());
+        let third = pool.acquire();
+        let mut keys = vec![first.key(), second.key(), third.key()];
+        keys.sort();
+        assert_eq!(keys, vec!["k0", "k1", "k2"]);
+
+        drop(second);
+        let idle = pool.pick();
+        assert_eq!(pool.slots[idle].in_flight.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn ejected_keys_are_skipped_until_all_are_ejected() {
+        let pool = pool(&[1, 1], KeyStrategy::RoundRobin);
+        let lease = pool.acquire();
+        let ejected = lease.key().to_string();
+        lease.report(reqwest::StatusCode::TOO_MANY_REQUESTS);
+        for _ in 0..4 {
+            assert_ne!(pool.acquire().key(), ejected);
+        }
+        // Other statuses leave the key in the pool
+        pool.acquire()
+            .report(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+
+        let other = pool.acquire();
+        other.report(reqwest::StatusCode::UNAUTHORIZED);
+        // Both cooling down: the one ejected first comes back first
+        assert_eq!(pool.acquire().key(), ejected);
+    }
+}
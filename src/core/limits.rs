@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::common::glob::glob_match;
+use crate::core::config::{AuthConfig, RateLimitConfig};
+
+/// Classic token bucket refilled continuously at `capacity` units per minute.
+///
+/// The level may go negative when actual token usage is debited after a response,
+/// which simply delays the next admission until the debt has been refilled.
+struct TokenBucket {
+    capacity: f64,
+    level: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(capacity: u64, now: Instant) -> Self {
+        Self {
+            capacity: capacity as f64,
+            level: capacity as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.level = (self.level + elapsed * self.capacity / 60.0).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds at least `amount` units.
+    fn time_until(&self, amount: f64) -> Duration {
+        if self.level >= amount || self.capacity <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64((amount - self.level) * 60.0 / self.capacity)
+    }
+
+    /// Time until the bucket is full again; what the `*-reset` headers report.
+    fn time_until_full(&self) -> Duration {
+        self.time_until(self.capacity)
+    }
+
+    fn remaining(&self) -> u64 {
+        self.level.max(0.0).floor() as u64
+    }
+
+    fn snapshot(&self) -> BucketSnapshot {
+        BucketSnapshot {
+            limit: self.capacity as u64,
+            remaining: self.remaining(),
+            reset: self.time_until_full(),
+        }
+    }
+}
+
+/// Point-in-time view of one bucket, rendered into rate-limit response headers.
+#[derive(Clone, Copy, Debug)]
+pub struct BucketSnapshot {
+    pub limit: u64,
+    pub remaining: u64,
+    pub reset: Duration,
+}
+
+/// The request and token buckets of the rule that limited a request.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateLimitStatus {
+    pub requests: Option<BucketSnapshot>,
+    pub tokens: Option<BucketSnapshot>,
+}
+
+/// Returned when a request exceeds one of its client's limits.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimited {
+    pub status: RateLimitStatus,
+    pub retry_after: Duration,
+}
+
+struct Rule {
+    /// `None` for the key-wide limit, otherwise a model name or glob.
+    pattern: Option<String>,
+    config: RateLimitConfig,
+}
+
+impl Rule {
+    /// Bucket of this rule for a request: one per key for the key-wide limit, one per
+    /// routing entry for a model rule, so `gpt-*` limits each matching routing entry
+    /// separately. Keying on the route rather than the requested name means a client cannot
+    /// get a fresh bucket by inventing another name the glob matches.
+    fn bucket_key(&self, client: &str, index: usize, route: &str) -> BucketKey {
+        let route = if self.pattern.is_some() { route } else { "" };
+        (client.to_string(), index, route.to_string())
+    }
+
+    fn applies_to(&self, model: &str) -> bool {
+        self.pattern
+            .as_deref()
+            .is_none_or(|pattern| glob_match(pattern, model))
+    }
+}
+
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+impl Buckets {
+    fn for_rule(config: &RateLimitConfig, now: Instant) -> Self {
+        Self {
+            requests: config
+                .requests_per_minute
+                .map(|rpm| TokenBucket::per_minute(rpm as u64, now)),
+            tokens: config
+                .tokens_per_minute
+                .map(|tpm| TokenBucket::per_minute(tpm, now)),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        self.requests.iter_mut().for_each(|b| b.refill(now));
+        self.tokens.iter_mut().for_each(|b| b.refill(now));
+    }
+
+    /// Whether both buckets have refilled completely, so dropping them loses nothing.
+    fn is_full(&self) -> bool {
+        self.requests
+            .iter()
+            .chain(self.tokens.iter())
+            .all(|b| b.time_until_full().is_zero())
+    }
+
+    fn retry_after(&self) -> Duration {
+        let requests = self
+            .requests
+            .as_ref()
+            .map(|b| b.time_until(1.0))
+            .unwrap_or_default();
+        // Token admission only needs a positive balance; real usage is debited later
+        let tokens = self
+            .tokens
+            .as_ref()
+            .map(|b| b.time_until(1.0))
+            .unwrap_or_default();
+        requests.max(tokens)
+    }
+
+    fn status(&self) -> RateLimitStatus {
+        RateLimitStatus {
+            requests: self.requests.as_ref().map(TokenBucket::snapshot),
+            tokens: self.tokens.as_ref().map(TokenBucket::snapshot),
+        }
+    }
+}
+
+/// Client, rule index and (for model rules) routing entry label.
+type BucketKey = (String, usize, String);
+
+/// How often buckets that have refilled completely are dropped.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct BucketMap {
+    buckets: HashMap<BucketKey, Buckets>,
+    swept: Instant,
+}
+
+impl BucketMap {
+    /// Drops full buckets, at most once per [`SWEEP_INTERVAL`]; a full bucket is
+    /// recreated identical on the next request.
+    fn sweep(&mut self, now: Instant) {
+        if now.duration_since(self.swept) < SWEEP_INTERVAL {
+            return;
+        }
+        self.swept = now;
+        self.buckets.retain(|_, entry| {
+            entry.refill(now);
+            !entry.is_full()
+        });
+    }
+}
+
+/// Per-client rate limiter for request count and tokens per minute.
+pub struct RateLimiter {
+    rules: HashMap<String, Vec<Rule>>,
+    buckets: Mutex<BucketMap>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &AuthConfig) -> Self {
+        let rules = config
+            .keys
+            .iter()
+            .map(|key| {
+                let key_wide = key.rate_limit.iter().map(|limit| Rule {
+                    pattern: None,
+                    config: limit.clone(),
+                });
+                let per_model = key.model_rate_limits.iter().map(|limit| Rule {
+                    pattern: Some(limit.pattern.clone()),
+                    config: limit.limit.clone(),
+                });
+                (key.name.clone(), key_wide.chain(per_model).collect())
+            })
+            .collect();
+
+        Self {
+            rules,
+            buckets: Mutex::new(BucketMap {
+                buckets: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    fn applicable<'a>(
+        &'a self,
+        client: &str,
+        model: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a Rule)> + 'a {
+        self.rules
+            .get(client)
+            .into_iter()
+            .flat_map(|rules| rules.iter().enumerate())
+            .filter(move |(_, rule)| rule.applies_to(model))
+    }
+
+    /// Admits a request for `model`, routed by the entry labelled `route`, consuming one unit
+    /// from every applicable request bucket.
+    ///
+    /// Nothing is consumed when any applicable limit is exhausted.
+    pub fn check(&self, client: &str, model: &str, route: &str) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut map = self.buckets.lock().unwrap();
+        map.sweep(now);
+        let buckets = &mut map.buckets;
+        let rules: Vec<(usize, &Rule)> = self.applicable(client, model).collect();
+
+        for (index, rule) in &rules {
+            let entry = buckets
+                .entry(rule.bucket_key(client, *index, route))
+                .or_insert_with(|| Buckets::for_rule(&rule.config, now));
+            entry.refill(now);
+
+            let retry_after = entry.retry_after();
+            if !retry_after.is_zero() {
+                return Err(RateLimited {
+                    status: entry.status(),
+                    retry_after,
+                });
+            }
+        }
+
+        for (index, rule) in &rules {
+            if let Some(requests) = buckets
+                .get_mut(&rule.bucket_key(client, *index, route))
+                .and_then(|entry| entry.requests.as_mut())
+            {
+                requests.level -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Debits the tokens a completed request actually used.
+    pub fn record_tokens(&self, client: &str, model: &str, route: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let buckets = &mut self.buckets.lock().unwrap().buckets;
+        for (index, rule) in self.applicable(client, model) {
+            let entry = buckets
+                .entry(rule.bucket_key(client, index, route))
+                .or_insert_with(|| Buckets::for_rule(&rule.config, now));
+            if let Some(bucket) = entry.tokens.as_mut() {
+                bucket.refill(now);
+                bucket.level -= tokens as f64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(key: &str) -> RateLimiter {
+        let config: AuthConfig =
+            serde_yaml::from_str(&format!("keys: [{{ name: a, key: k, {} }}]", key)).unwrap();
+        RateLimiter::new(&config)
+    }
+
+    #[test]
+    fn key_wide_limit_is_shared_by_all_models() {
+        let limiter = limiter("rate_limit: { requests_per_minute: 2 }");
+        assert!(limiter.check("a", "gpt-4o", "gpt-4o").is_ok());
+        assert!(limiter.check("a", "claude", "other").is_ok());
+        let limited = limiter.check("a", "gpt-4o", "gpt-4o").unwrap_err();
+        assert_eq!(limited.status.requests.unwrap().remaining, 0);
+        assert!(!limited.retry_after.is_zero());
+    }
+
+    #[test]
+    fn glob_rule_limits_each_routing_entry_separately() {
+        let limiter = limiter("model_rate_limits: [{ match: \"gpt-*\", requests_per_minute: 1 }]");
+        assert!(limiter.check("a", "gpt-4o", "gpt-4o").is_ok());
+        assert!(limiter.check("a", "gpt-4o-mini", "gpt-4o-mini").is_ok());
+        assert!(limiter.check("a", "gpt-4o", "gpt-4o").is_err());
+        // Models the rule does not match and unknown clients are unaffected
+        assert!(limiter.check("a", "claude", "other").is_ok());
+        assert!(limiter.check("b", "gpt-4o", "gpt-4o").is_ok());
+    }
+
+    #[test]
+    fn new_names_on_the_same_route_share_a_bucket() {
+        let limiter = limiter("model_rate_limits: [{ match: \"gpt-*\", requests_per_minute: 1 }]");
+        assert!(limiter.check("a", "gpt-a", "other").is_ok());
+        assert!(limiter.check("a", "gpt-b", "other").is_err());
+    }
+
+    #[test]
+    fn token_debt_delays_admission() {
+        let limiter = limiter("rate_limit: { tokens_per_minute: 100 }");
+        assert!(limiter.check("a", "m", "other").is_ok());
+        limiter.record_tokens("a", "m", "other", 150);
+        assert!(limiter.check("a", "m", "other").is_err());
+    }
+
+    #[test]
+    fn only_full_buckets_are_swept() {
+        let limiter = limiter("model_rate_limits: [{ match: \"*\", requests_per_minute: 60 }]");
+        for route in ["x", "y"] {
+            assert!(limiter.check("a", "m", route).is_ok());
+        }
+        let later = Instant::now() + SWEEP_INTERVAL;
+        let mut map = limiter.buckets.lock().unwrap();
+        // Debt on `y` keeps it short of full a minute later
+        map.buckets
+            .get_mut(&("a".into(), 0, "y".into()))
+            .unwrap()
+            .requests
+            .as_mut()
+            .unwrap()
+            .level -= 60.0;
+        map.sweep(later);
+        assert_eq!(map.buckets.len(), 1);
+        assert!(map.buckets.contains_key(&("a".into(), 0, "y".into())));
+    }
+}
@@ -0,0 +1,282 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::core::config::BatchConfig;
+use crate::core::retry::RetryPolicy;
+
+/// How long a batch may take before its unprocessed requests expire.
+const BATCH_LIFETIME_HOURS: i64 = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    InProgress,
+    /// Cancel requested; requests not yet sent are being marked `canceled`.
+    Canceling,
+    Ended,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct RequestCounts {
+    pub processing: u64,
+    pub succeeded: u64,
+    pub errored: u64,
+    pub canceled: u64,
+    pub expired: u64,
+}
+
+/// Stored state of one batch, kept in `{id}.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MessageBatch {
+    pub id: String,
+    /// Virtual key name that created the batch; only it can see the batch.
+    pub client: String,
+    pub processing_status: ProcessingStatus,
+    pub request_counts: RequestCounts,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub cancel_initiated_at: Option<DateTime<Utc>>,
+}
+
+impl MessageBatch {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+}
+
+/// One submitted request: the Messages API parameters and the caller's id for them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub custom_id: String,
+    pub params: Value,
+}
+
+/// Message batches kept on disk so they survive restarts.
+///
+/// Each batch is three files: `{id}.json` (state), `{id}.requests.jsonl` (as submitted)
+/// and `{id}.results.jsonl` (appended as requests finish, in Anthropic's result format).
+pub struct MessageBatchStore {
+    dir: PathBuf,
+    concurrency: usize,
+    retry: RetryPolicy,
+    batches: Mutex<HashMap<String, MessageBatch>>,
+}
+
+impl MessageBatchStore {
+    pub fn open(config: &BatchConfig) -> Self {
+        let dir = config.batch_dir();
+        if let Err(e) = fs::create_dir_all(&dir) {
+            error!(error = %e, dir = %dir.display(), "Cannot create batch directory");
+        }
+
+        let mut batches = HashMap::new();
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+                let is_state = path.extension().is_some_and(|ext| ext == "json")
+                    && path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with("msgbatch_"));
+                if !is_state {
+                    continue;
+                }
+                match fs::read(&path)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<MessageBatch>(&bytes).ok())
+                {
+                    Some(batch) => {
+                        batches.insert(batch.id.clone(), batch);
+                    }
+                    None => warn!(path = %path.display(), "Skipped unreadable batch file"),
+                }
+            }
+            info!(batches = batches.len(), dir = %dir.display(), "Loaded message batches");
+        }
+
+        Self {
+            dir,
+            concurrency: config.concurrency.max(1),
+            retry: RetryPolicy::new(config.retry.clone()),
+            batches: Mutex::new(batches),
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Retry policy for single requests of a batch.
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Stores a new batch; its requests are all `processing` until the runner picks them up.
+    pub fn create(&self, client: &str, requests: &[BatchRequest]) -> io::Result<MessageBatch> {
+        let now = Utc::now();
+        let batch = MessageBatch {
+            id: format!("msgbatch_{}", Uuid::new_v4().simple()),
+            client: client.to_string(),
+            processing_status: ProcessingStatus::InProgress,
+            request_counts: RequestCounts {
+                processing: requests.len() as u64,
+                ..Default::default()
+            },
+            created_at: now,
+            expires_at: now + Duration::hours(BATCH_LIFETIME_HOURS),
+            ended_at: None,
+            cancel_initiated_at: None,
+        };
+
+        let mut file = File::create(self.path(&batch.id, "requests.jsonl"))?;
+        for request in requests {
+            writeln!(file, "{}", serde_json::to_string(request)?)?;
+        }
+        self.save(&batch)?;
+        self.batches
+            .lock()
+            .unwrap()
+            .insert(batch.id.clone(), batch.clone());
+        Ok(batch)
+    }
+
+    pub fn get(&self, id: &str) -> Option<MessageBatch> {
+        self.batches.lock().unwrap().get(id).cloned()
+    }
+
+    /// A client's batches, most recently created first.
+    pub fn list(&self, client: &str) -> Vec<MessageBatch> {
+        let mut batches: Vec<MessageBatch> = self
+            .batches
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|b| b.client == client)
+            .cloned()
+            .collect();
+        batches.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        batches
+    }
+
+    /// Batches the runner has not finished, e.g. because the proxy restarted.
+    pub fn unfinished(&self) -> Vec<MessageBatch> {
+        self.batches
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|b| b.processing_status != ProcessingStatus::Ended)
+            .cloned()
+            .collect()
+    }
+
+    pub fn requests(&self, id: &str) -> io::Result<Vec<BatchRequest>> {
+        let file = File::open(self.path(id, "requests.jsonl"))?;
+        BufReader::new(file)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+
+    /// `custom_id`s that already have a result.
+    pub fn completed_ids(&self, id: &str) -> HashSet<String> {
+        let Ok(file) = File::open(self.path(id, "results.jsonl")) else {
+            return HashSet::new();
+        };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+            .filter_map(|line| line.get("custom_id")?.as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Appends a request's result and moves it from `processing` to the result's type.
+    pub fn record_result(&self, id: &str, custom_id: &str, result: Value) {
+        let line = serde_json::json!({ "custom_id": custom_id, "result": result });
+        // Held while appending so results of concurrent requests never interleave
+        let mut batches = self.batches.lock().unwrap();
+        let Some(batch) = batches.get_mut(id) else {
+            return;
+        };
+        let appended = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(id, "results.jsonl"))
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = appended {
+            error!(error = %e, batch = id, "Failed to append batch result");
+            return;
+        }
+
+        let counts = &mut batch.request_counts;
+        counts.processing = counts.processing.saturating_sub(1);
+        match line.pointer("/result/type").and_then(|t| t.as_str()) {
+            Some("succeeded") => counts.succeeded += 1,
+            Some("canceled") => counts.canceled += 1,
+            Some("expired") => counts.expired += 1,
+            _ => counts.errored += 1,
+        }
+        if let Err(e) = self.save(batch) {
+            error!(error = %e, batch = id, "Failed to save batch state");
+        }
+    }
+
+    /// Requests cancellation of a batch that is still running.
+    pub fn cancel(&self, id: &str) -> Option<MessageBatch> {
+        self.update(id, |batch| {
+            if batch.processing_status == ProcessingStatus::InProgress {
+                batch.processing_status = ProcessingStatus::Canceling;
+                batch.cancel_initiated_at = Some(Utc::now());
+            }
+        })
+    }
+
+    pub fn finish(&self, id: &str) -> Option<MessageBatch> {
+        self.update(id, |batch| {
+            batch.processing_status = ProcessingStatus::Ended;
+            batch.ended_at = Some(Utc::now());
+        })
+    }
+
+    pub fn results(&self, id: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(id, "results.jsonl"))
+    }
+
+    /// Removes an ended batch and its files.
+    pub fn delete(&self, id: &str) {
+        self.batches.lock().unwrap().remove(id);
+        for suffix in ["json", "requests.jsonl", "results.jsonl"] {
+            let _ = fs::remove_file(self.path(id, suffix));
+        }
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut MessageBatch)) -> Option<MessageBatch> {
+        let mut batches = self.batches.lock().unwrap();
+        let batch = batches.get_mut(id)?;
+        change(batch);
+        if let Err(e) = self.save(batch) {
+            error!(error = %e, batch = id, "Failed to save batch state");
+        }
+        Some(batch.clone())
+    }
+
+    /// Writes the state file through a temporary file so a crash never leaves it truncated.
+    fn save(&self, batch: &MessageBatch) -> io::Result<()> {
+        let path = self.path(&batch.id, "json");
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(batch)?)?;
+        fs::rename(&tmp, &path)
+    }
+
+    fn path(&self, id: &str, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, suffix))
+    }
+}
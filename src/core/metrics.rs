@@ -0,0 +1,284 @@
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::core::router::ResolvedRoute;
+use crate::core::usage::Usage;
+
+/// Label value used before a request has been routed to an upstream.
+const NO_UPSTREAM: &str = "none";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RouteLabels {
+    route: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    route: String,
+    model: String,
+    upstream: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StatusLabels {
+    route: String,
+    model: String,
+    upstream: String,
+    status: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TokenLabels {
+    route: String,
+    model: String,
+    upstream: String,
+    direction: String,
+}
+
+fn latency_histogram() -> Histogram {
+    // 50ms .. ~7min
+    Histogram::new(exponential_buckets(0.05, 2.0, 14))
+}
+
+/// Prometheus metrics for requests flowing through the proxy.
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<StatusLabels, Counter>,
+    upstream_responses: Family<StatusLabels, Counter>,
+    duration: Family<RequestLabels, Histogram>,
+    time_to_first_byte: Family<RequestLabels, Histogram>,
+    stream_aborts: Family<RequestLabels, Counter>,
+    tokens: Family<TokenLabels, Counter>,
+    in_flight: Family<RouteLabels, Gauge>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("proxy");
+
+        let requests = Family::<StatusLabels, Counter>::default();
+        registry.register(
+            "requests",
+            "Client requests by the status returned to the client",
+            requests.clone(),
+        );
+        let upstream_responses = Family::<StatusLabels, Counter>::default();
+        registry.register(
+            "upstream_responses",
+            "Final upstream response status per request (`error` when no response was received)",
+            upstream_responses.clone(),
+        );
+        let duration = Family::<RequestLabels, Histogram>::new_with_constructor(
+            latency_histogram as fn() -> _,
+        );
+        registry.register(
+            "request_duration_seconds",
+            "Total request latency, until the last byte of a stream",
+            duration.clone(),
+        );
+        let time_to_first_byte = Family::<RequestLabels, Histogram>::new_with_constructor(
+            latency_histogram as fn() -> _,
+        );
+        registry.register(
+            "time_to_first_byte_seconds",
+            "Latency until the upstream response (or first stream chunk) arrived",
+            time_to_first_byte.clone(),
+        );
+        let stream_aborts = Family::<RequestLabels, Counter>::default();
+        registry.register(
+            "stream_aborts",
+            "Streams that ended early because the client disconnected",
+            stream_aborts.clone(),
+        );
+        let tokens = Family::<TokenLabels, Counter>::default();
+        registry.register(
+            "tokens",
+            "Tokens reported by upstreams, by direction (`input`/`output`)",
+            tokens.clone(),
+        );
+        let in_flight = Family::<RouteLabels, Gauge>::default();
+        registry.register(
+            "in_flight_requests",
+            "Requests currently being served, including open streams",
+            in_flight.clone(),
+        );
+
+        Self {
+            registry,
+            requests,
+            upstream_responses,
+            duration,
+            time_to_first_byte,
+            stream_aborts,
+            tokens,
+            in_flight,
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).expect("Writing to a String cannot fail");
+        buffer
+    }
+
+    /// Starts tracking one client request; it counts as in flight until every clone is dropped.
+    pub fn track(self: &Arc<Self>, route: &str) -> RequestTracker {
+        let route = route.to_string();
+        self.in_flight
+            .get_or_create(&RouteLabels {
+                route: route.clone(),
+            })
+            .inc();
+        RequestTracker {
+            inner: Arc::new(TrackerInner {
+                metrics: self.clone(),
+                started: Instant::now(),
+                route,
+                target: Mutex::new((String::new(), NO_UPSTREAM.to_string())),
+                status: AtomicU16::new(0),
+                first_byte: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct TrackerInner {
+    metrics: Arc<Metrics>,
+    started: Instant,
+    route: String,
+    /// (model, upstream)
+    target: Mutex<(String, String)>,
+    status: AtomicU16,
+    first_byte: AtomicBool,
+}
+
+impl TrackerInner {
+    fn labels(&self) -> RequestLabels {
+        let (model, upstream) = self.target.lock().unwrap().clone();
+        RequestLabels {
+            route: self.route.clone(),
+            model,
+            upstream,
+        }
+    }
+
+    fn with_status(&self, status: String) -> StatusLabels {
+        let labels = self.labels();
+        StatusLabels {
+            route: labels.route,
+            model: labels.model,
+            upstream: labels.upstream,
+            status,
+        }
+    }
+}
+
+impl Drop for TrackerInner {
+    fn drop(&mut self) {
+        let metrics = &self.metrics;
+        let status = self.status.load(Ordering::Relaxed);
+        metrics
+            .requests
+            .get_or_create(&self.with_status(status.to_string()))
+            .inc();
+        metrics
+            .duration
+            .get_or_create(&self.labels())
+            .observe(self.started.elapsed().as_secs_f64());
+        metrics
+            .in_flight
+            .get_or_create(&RouteLabels {
+                route: self.route.clone(),
+            })
+            .dec();
+    }
+}
+
+/// Per-request handle shared between the handler and its streaming task.
+///
+/// The request is recorded once the last clone is dropped, so streams are timed to their end.
+#[derive(Clone)]
+pub struct RequestTracker {
+    inner: Arc<TrackerInner>,
+}
+
+impl RequestTracker {
+    /// Sets the model label from the route's config entry, so clients can't grow the label set
+    /// with arbitrary model names, and the upstream serving it.
+    pub fn set_target(&self, route: &ResolvedRoute) {
+        *self.inner.target.lock().unwrap() =
+            (route.label().to_string(), route.primary().provider.clone());
+    }
+
+    pub fn set_upstream(&self, upstream: &str) {
+        self.inner.target.lock().unwrap().1 = upstream.to_string();
+    }
+
+    /// Status the proxy answered the client with.
+    pub fn respond(&self, status: u16) {
+        self.inner.status.store(status, Ordering::Relaxed);
+    }
+
+    /// Final upstream status, or `None` when every upstream attempt failed to send.
+    pub fn upstream_status(&self, status: Option<u16>) {
+        let status = status.map_or_else(|| "error".to_string(), |s| s.to_string());
+        self.inner
+            .metrics
+            .upstream_responses
+            .get_or_create(&self.inner.with_status(status))
+            .inc();
+    }
+
+    /// Records time to first byte; only the first call counts.
+    pub fn first_byte(&self) {
+        if !self.inner.first_byte.swap(true, Ordering::Relaxed) {
+            self.inner
+                .metrics
+                .time_to_first_byte
+                .get_or_create(&self.inner.labels())
+                .observe(self.inner.started.elapsed().as_secs_f64());
+        }
+    }
+
+    pub fn stream_aborted(&self) {
+        self.inner
+            .metrics
+            .stream_aborts
+            .get_or_create(&self.inner.labels())
+            .inc();
+    }
+
+    pub fn record_tokens(&self, usage: &Usage) {
+        let labels = self.inner.labels();
+        for (direction, count) in [
+            ("input", usage.input_tokens),
+            ("output", usage.output_tokens),
+        ] {
+            self.inner
+                .metrics
+                .tokens
+                .get_or_create(&TokenLabels {
+                    route: labels.route.clone(),
+                    model: labels.model.clone(),
+                    upstream: labels.upstream.clone(),
+                    direction: direction.to_string(),
+                })
+                .inc_by(count);
+        }
+    }
+}
@@ -1,5 +1,21 @@
+pub mod accounting;
+pub mod batches;
 pub mod client;
 pub mod config;
+pub mod files;
+pub mod health;
+pub mod keys;
+pub mod limits;
+pub mod message_batches;
+pub mod metrics;
+pub mod pricing;
+pub mod profile;
+pub mod retry;
+pub mod router;
+pub mod signing;
+pub mod tokenizer;
+pub mod usage;
 
 pub use client::OpenAiClient;
 pub use config::Config;
+pub use router::ModelRouter;
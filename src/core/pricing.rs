@@ -0,0 +1,49 @@
+use crate::common::glob::glob_match;
+use crate::core::config::{ModelPriceConfig, PricingConfig};
+use crate::core::usage::Usage;
+
+/// Response header carrying the estimated cost of a non-streaming request.
+pub const COST_HEADER: &str = "x-proxy-cost-usd";
+
+const TOKENS_PER_UNIT: f64 = 1_000_000.0;
+
+/// Looks up upstream model prices and turns token usage into an estimated cost.
+pub struct PriceTable {
+    prices: Vec<ModelPriceConfig>,
+    cost_header: bool,
+}
+
+impl PriceTable {
+    pub fn new(config: &PricingConfig) -> Self {
+        Self {
+            prices: config.models.clone(),
+            cost_header: config.cost_header,
+        }
+    }
+
+    /// Whether non-streaming responses should carry `x-proxy-cost-usd`.
+    pub fn cost_header(&self) -> bool {
+        self.cost_header
+    }
+
+    /// Estimated USD cost of `usage` on `model` at `provider`, or `None` when unpriced.
+    ///
+    /// The first entry matching the model wins; entries scoped to another provider are skipped.
+    pub fn cost(&self, provider: &str, model: &str, usage: &Usage) -> Option<f64> {
+        let price = self.prices.iter().find(|price| {
+            price.provider.as_deref().is_none_or(|p| p == provider)
+                && glob_match(&price.pattern, model)
+        })?;
+
+        let cached = usage.cached_tokens.min(usage.input_tokens);
+        let uncached = usage.input_tokens - cached;
+        let cached_price = price.cached_input.unwrap_or(price.input);
+
+        Some(
+            (uncached as f64 * price.input
+                + cached as f64 * cached_price
+                + usage.output_tokens as f64 * price.output)
+                / TOKENS_PER_UNIT,
+        )
+    }
+}
@@ -0,0 +1,555 @@
+use serde_json::{json, Map, Value};
+
+use crate::common::glob::glob_match;
+use crate::common::tool_ids::split_tool_call_id;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tracing::warn;
+
+use crate::core::config::{PdfHandling, ProviderProfile, ThinkingStyle, ToolImages};
+
+/// Effort thresholds for models without a matching `effort_thresholds` entry.
+const DEFAULT_LOW_BUDGET: u64 = 4096;
+const DEFAULT_MEDIUM_BUDGET: u64 = 16384;
+
+/// Thinking as the client asked for it, whichever front-end it came through.
+struct Thinking {
+    enabled: bool,
+    /// `low`/`medium`/`high` (or whatever the client sent); absent when the model decides.
+    effort: Option<String>,
+    budget: Option<u64>,
+}
+
+/// Marks tool results flagged `is_error` when the profile doesn't set a prefix.
+const DEFAULT_TOOL_ERROR_PREFIX: &str = "Error: ";
+
+/// Stands in for image parts sent to a profile with `drop_images`.
+const IMAGE_PLACEHOLDER: &str = "[image omitted]";
+
+/// Rewrites an OpenAI-format request into the shape an OpenAI-kind provider accepts.
+///
+/// Thinking is translated first, so `drop`/`rename`/`inject` also see the fields it produced;
+/// the declarative rules then run in the order replace, drop, rename, inject. PDFs are
+/// handled separately by [`extract_pdfs`], which has to run off the async runtime.
+pub fn apply(profile: &ProviderProfile, body: &mut Value) {
+    let Some(body) = body.as_object_mut() else {
+        return;
+    };
+    translate_thinking(profile, body);
+    strip_reasoning_history(body, profile.replay_reasoning);
+    strip_thought_signatures(body);
+    shape_tool_results(profile, body);
+    if profile.drop_images {
+        replace_images(body);
+    }
+
+    for (path, values) in &profile.replace {
+        let replacement = lookup(body, path)
+            .and_then(|v| v.as_str())
+            .and_then(|v| values.get(v));
+        if let Some(replacement) = replacement {
+            insert(body, path, json!(replacement));
+        }
+    }
+    for path in &profile.drop {
+        take(body, path);
+    }
+    for (from, to) in &profile.rename {
+        if let Some(value) = take(body, from) {
+            insert(body, to, value);
+        }
+    }
+    for (path, value) in &profile.inject {
+        insert(body, path, value.clone());
+    }
+}
+
+/// Turns `is_error` on tool messages into a text prefix and, unless the profile takes images
+/// in tool content, moves tool images and files into a user message after the run of tool results
+/// (a message in between would separate results from their tool calls).
+fn shape_tool_results(profile: &ProviderProfile, body: &mut Map<String, Value>) {
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return;
+    };
+    let prefix = profile
+        .tool_error_prefix
+        .as_deref()
+        .unwrap_or(DEFAULT_TOOL_ERROR_PREFIX);
+
+    let mut shaped = Vec::with_capacity(messages.len());
+    let mut images: Vec<Value> = Vec::new();
+    for mut message in messages.drain(..) {
+        let is_tool = message.get("role").and_then(|r| r.as_str()) == Some("tool");
+        if !is_tool && !images.is_empty() {
+            shaped.push(json!({ "role": "user", "content": std::mem::take(&mut images) }));
+        }
+        if let (true, Some(message)) = (is_tool, message.as_object_mut()) {
+            if message.remove("is_error").and_then(|e| e.as_bool()) == Some(true) {
+                prefix_content(message, prefix);
+            }
+            if profile.tool_images == ToolImages::UserMessage {
+                images.extend(take_attachments(message));
+            }
+        }
+        shaped.push(message);
+    }
+    if !images.is_empty() {
+        shaped.push(json!({ "role": "user", "content": images }));
+    }
+    *messages = shaped;
+}
+
+fn prefix_content(message: &mut Map<String, Value>, prefix: &str) {
+    match message.get_mut("content") {
+        Some(Value::String(text)) => text.insert_str(0, prefix),
+        Some(Value::Array(parts)) => {
+            let first_text = parts.iter_mut().find_map(|p| match p.get_mut("text") {
+                Some(Value::String(text)) => Some(text),
+                _ => None,
+            });
+            match first_text {
+                Some(text) => text.insert_str(0, prefix),
+                None => parts.insert(0, json!({ "type": "text", "text": prefix })),
+            }
+        }
+        _ => {
+            message.insert("content".to_string(), json!(prefix));
+        }
+    }
+}
+
+/// Removes image and file parts from a tool message, leaving its text as a plain string;
+/// they come back introduced by a line naming the tool call.
+fn take_attachments(message: &mut Map<String, Value>) -> Vec<Value> {
+    let Some(Value::Array(parts)) = message.get("content") else {
+        return Vec::new();
+    };
+    let (images, text): (Vec<Value>, Vec<Value>) = parts.iter().cloned().partition(|p| {
+        matches!(
+            p.get("type").and_then(|t| t.as_str()),
+            Some("image_url" | "file")
+        )
+    });
+    if images.is_empty() {
+        return Vec::new();
+    }
+
+    let text = text
+        .iter()
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    message.insert("content".to_string(), json!(text));
+
+    let id = message
+        .get("tool_call_id")
+        .and_then(|i| i.as_str())
+        .unwrap_or("");
+    let intro =
+        json!({ "type": "text", "text": format!("Attachments returned by tool call {}:", id) });
+    std::iter::once(intro).chain(images).collect()
+}
+
+/// For profiles with `pdf: text`, replaces base64 PDF `file` parts in every message with their
+/// text. Extraction is CPU-bound and can take seconds on a large file, so it runs on the
+/// blocking pool; call it before [`apply`], which may move tool result files elsewhere.
+pub async fn extract_pdfs(profile: &ProviderProfile, mut body: Value) -> Value {
+    if profile.pdf != PdfHandling::Text {
+        return body;
+    }
+    tokio::task::spawn_blocking(move || {
+        if let Some(map) = body.as_object_mut() {
+            pdfs_to_text(map);
+        }
+        body
+    })
+    .await
+    .expect("PDF extraction panicked outside catch_unwind")
+}
+
+fn pdfs_to_text(body: &mut Map<String, Value>) {
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return;
+    };
+    for content in messages.iter_mut().filter_map(|m| m.get_mut("content")) {
+        let Value::Array(parts) = content else {
+            continue;
+        };
+        for part in parts.iter_mut() {
+            let Some(file) = part.get("file").filter(|_| part["type"] == "file") else {
+                continue;
+            };
+            let Some(data) = file
+                .get("file_data")
+                .and_then(|d| d.as_str())
+                .and_then(|d| d.strip_prefix("data:application/pdf;base64,"))
+            else {
+                continue;
+            };
+            let filename = file
+                .get("filename")
+                .and_then(|f| f.as_str())
+                .unwrap_or("document.pdf")
+                .to_string();
+            let text = match pdf_text(data) {
+                Some(text) => format!("Document: {}\n\n{}", filename, text.trim()),
+                None => {
+                    warn!(filename = %filename, "Could not extract text from PDF");
+                    format!("[PDF {} could not be read]", filename)
+                }
+            };
+            *part = json!({ "type": "text", "text": text });
+        }
+    }
+}
+
+fn pdf_text(data: &str) -> Option<String> {
+    let bytes = STANDARD.decode(data).ok()?;
+    // pdf-extract panics on some malformed files rather than returning an error
+    std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(&bytes))
+        .ok()?
+        .ok()
+}
+
+fn lookup<'a>(body: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let (parents, last) = split_path(path);
+    let mut current = body;
+    for key in parents {
+        current = current.get(key)?.as_object()?;
+    }
+    current.get(last)
+}
+
+/// Removes a dotted path, returning what was there.
+fn take(body: &mut Map<String, Value>, path: &str) -> Option<Value> {
+    let (parents, last) = split_path(path);
+    let mut current = body;
+    for key in parents {
+        current = current.get_mut(key)?.as_object_mut()?;
+    }
+    current.remove(last)
+}
+
+/// Sets a dotted path, creating (or replacing non-object) parents on the way.
+fn insert(body: &mut Map<String, Value>, path: &str, value: Value) {
+    let (parents, last) = split_path(path);
+    let mut current = body;
+    for key in parents {
+        let child = current.entry(key).or_insert_with(|| json!({}));
+        if !child.is_object() {
+            *child = json!({});
+        }
+        current = child.as_object_mut().expect("just made an object");
+    }
+    current.insert(last.to_string(), value);
+}
+
+fn split_path(path: &str) -> (Vec<&str>, &str) {
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap_or_default();
+    (keys, last)
+}
+
+/// Cuts the Gemini thought signatures out of tool call ids; OpenAI caps ids at 40 characters.
+fn strip_thought_signatures(body: &mut Map<String, Value>) {
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return;
+    };
+    let strip = |id: &mut Value| {
+        if let Some(plain) = id.as_str().map(|i| split_tool_call_id(i).0.to_string()) {
+            *id = json!(plain);
+        }
+    };
+    for message in messages.iter_mut() {
+        if let Some(id) = message.get_mut("tool_call_id") {
+            strip(id);
+        }
+        if let Some(Value::Array(calls)) = message.get_mut("tool_calls") {
+            for id in calls.iter_mut().filter_map(|c| c.get_mut("id")) {
+                strip(id);
+            }
+        }
+    }
+}
+
+/// Swaps `image_url` parts of every message for a text placeholder.
+fn replace_images(body: &mut Map<String, Value>) {
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return;
+    };
+    for content in messages.iter_mut().filter_map(|m| m.get_mut("content")) {
+        let Value::Array(parts) = content else {
+            continue;
+        };
+        for part in parts.iter_mut() {
+            if part.get("type").and_then(|t| t.as_str()) == Some("image_url") {
+                *part = json!({ "type": "text", "text": IMAGE_PLACEHOLDER });
+            }
+        }
+    }
+}
+
+/// Removes the Anthropic-only `reasoning_signature` from earlier assistant turns, and unless
+/// `keep_reasoning` their `reasoning_content` too, which most upstreams reject.
+fn strip_reasoning_history(body: &mut Map<String, Value>, keep_reasoning: bool) {
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return;
+    };
+    for message in messages.iter_mut().filter_map(|m| m.as_object_mut()) {
+        message.remove("reasoning_signature");
+        if !keep_reasoning {
+            message.remove("reasoning_content");
+        }
+    }
+}
+
+/// Replaces Anthropic's `thinking` object (kept by `/v1/messages`) and `reasoning_effort`
+/// with the single field the profile's thinking style names.
+fn translate_thinking(profile: &ProviderProfile, body: &mut Map<String, Value>) {
+    let thinking = body.remove("thinking");
+    let effort = body.remove("reasoning_effort");
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+
+    let requested = match thinking.as_ref().and_then(|t| t.get("type")?.as_str()) {
+        Some("enabled") => {
+            let budget = thinking
+                .as_ref()
+                .and_then(|t| t.get("budget_tokens")?.as_u64());
+            Thinking {
+                enabled: true,
+                effort: budget.map(|budget| effort_for_budget(profile, model, budget).to_string()),
+                budget,
+            }
+        }
+        Some("adaptive") => Thinking {
+            enabled: true,
+            effort: None,
+            budget: None,
+        },
+        Some("disabled") => Thinking {
+            enabled: false,
+            effort: None,
+            budget: None,
+        },
+        _ => match effort.as_ref().and_then(|e| e.as_str()) {
+            Some(effort) => Thinking {
+                enabled: effort != "none",
+                effort: Some(effort.to_string()),
+                budget: None,
+            },
+            None => return,
+        },
+    };
+
+    match profile.thinking {
+        ThinkingStyle::ReasoningEffort => {
+            if let Some(effort) = requested.effort {
+                body.insert("reasoning_effort".to_string(), json!(effort));
+            }
+        }
+        ThinkingStyle::Reasoning => {
+            let reasoning = match (requested.enabled, requested.budget, requested.effort) {
+                (false, _, _) => json!({ "enabled": false }),
+                (true, Some(budget), _) => json!({ "max_tokens": budget }),
+                (true, None, Some(effort)) => json!({ "effort": effort }),
+                (true, None, None) => json!({ "enabled": true }),
+            };
+            body.insert("reasoning".to_string(), reasoning);
+        }
+        ThinkingStyle::ChatTemplateKwargs => {
+            let kwargs = body
+                .entry("chat_template_kwargs")
+                .or_insert_with(|| json!({}));
+            if let Some(kwargs) = kwargs.as_object_mut() {
+                kwargs.insert("enable_thinking".to_string(), json!(requested.enabled));
+            }
+        }
+        ThinkingStyle::Omit => {}
+    }
+}
+
+/// Thinking budget standing for a `reasoning_effort`, for upstreams that take a budget. Each
+/// lands in its own band of the default thresholds; `none` and unknown values give `None`.
+pub fn budget_for_effort(effort: &str) -> Option<u64> {
+    match effort {
+        "minimal" => Some(1024),
+        "low" => Some(DEFAULT_LOW_BUDGET),
+        "medium" => Some(DEFAULT_MEDIUM_BUDGET),
+        "high" => Some(2 * DEFAULT_MEDIUM_BUDGET),
+        _ => None,
+    }
+}
+
+fn effort_for_budget(profile: &ProviderProfile, model: &str, budget: u64) -> &'static str {
+    let (low, medium) = profile
+        .effort_thresholds
+        .iter()
+        .find(|t| glob_match(&t.pattern, model))
+        .map_or((DEFAULT_LOW_BUDGET, DEFAULT_MEDIUM_BUDGET), |t| {
+            (t.low, t.medium)
+        });
+    if budget <= low {
+        "low"
+    } else if budget <= medium {
+        "medium"
+    } else {
+        "high"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(yaml: &str) -> ProviderProfile {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn rules_run_in_order_replace_drop_rename_inject() {
+        let profile = profile(
+            r#"
+thinking: none
+replace: { tool_choice: { required: auto } }
+drop: [stream_options, chat_template_kwargs.enable_thinking]
+rename: { max_tokens: max_completion_tokens, tool_choice: options.tool_choice }
+inject: { max_completion_tokens: 512, options.seed: 7 }
+"#,
+        );
+        let mut body = json!({
+            "model": "m",
+            "max_tokens": 100,
+            "tool_choice": "required",
+            "stream_options": { "include_usage": true },
+            "chat_template_kwargs": { "enable_thinking": true, "other": 1 }
+        });
+        apply(&profile, &mut body);
+        assert_eq!(
+            body,
+            json!({
+                "model": "m",
+                "max_completion_tokens": 512,
+                "options": { "tool_choice": "auto", "seed": 7 },
+                "chat_template_kwargs": { "other": 1 }
+            })
+        );
+    }
+
+    #[test]
+    fn rules_leave_missing_and_unlisted_values_alone() {
+        let profile = profile(
+            r#"
+replace: { tool_choice: { required: auto } }
+drop: [a.b.c]
+rename: { absent: present }
+"#,
+        );
+        let mut body = json!({ "tool_choice": "none", "a": "scalar" });
+        apply(&profile, &mut body);
+        assert_eq!(body, json!({ "tool_choice": "none", "a": "scalar" }));
+    }
+
+    fn thinking_for(style: &str, request: Value) -> Value {
+        let mut body = request;
+        apply(&profile(&format!("thinking: {}", style)), &mut body);
+        body
+    }
+
+    #[test]
+    fn thinking_budgets_map_to_each_style() {
+        let enabled =
+            json!({ "model": "m", "thinking": { "type": "enabled", "budget_tokens": 8000 } });
+        assert_eq!(
+            thinking_for("reasoning_effort", enabled.clone()),
+            json!({ "model": "m", "reasoning_effort": "medium" })
+        );
+        assert_eq!(
+            thinking_for("reasoning", enabled.clone()),
+            json!({ "model": "m", "reasoning": { "max_tokens": 8000 } })
+        );
+        assert_eq!(
+            thinking_for("chat_template_kwargs", enabled.clone()),
+            json!({ "model": "m", "chat_template_kwargs": { "enable_thinking": true } })
+        );
+        assert_eq!(thinking_for("none", enabled), json!({ "model": "m" }));
+
+        let disabled = json!({ "model": "m", "reasoning_effort": "none" });
+        assert_eq!(
+            thinking_for("reasoning", disabled.clone()),
+            json!({ "model": "m", "reasoning": { "enabled": false } })
+        );
+        assert_eq!(
+            thinking_for("chat_template_kwargs", disabled),
+            json!({ "model": "m", "chat_template_kwargs": { "enable_thinking": false } })
+        );
+    }
+
+    #[test]
+    fn effort_thresholds_match_the_model() {
+        let profile = profile(
+            r#"
+effort_thresholds:
+  - { match: "small-*", low: 1000, medium: 2000 }
+"#,
+        );
+        for (model, effort) in [("small-1", "high"), ("large-1", "low")] {
+            let mut body =
+                json!({ "model": model, "thinking": { "type": "enabled", "budget_tokens": 3000 } });
+            apply(&profile, &mut body);
+            assert_eq!(body["reasoning_effort"], effort, "{}", model);
+        }
+    }
+
+    #[test]
+    fn reasoning_history_is_kept_only_when_replayed() {
+        let request = json!({ "messages": [
+            { "role": "assistant", "content": "hi", "reasoning_content": "hmm", "reasoning_signature": "sig" }
+        ] });
+        let mut body = request.clone();
+        apply(&profile("replay_reasoning: true"), &mut body);
+        assert_eq!(
+            body["messages"][0],
+            json!({ "role": "assistant", "content": "hi", "reasoning_content": "hmm" })
+        );
+        let mut body = request;
+        apply(&profile("{}"), &mut body);
+        assert_eq!(
+            body["messages"][0],
+            json!({ "role": "assistant", "content": "hi" })
+        );
+    }
+
+    #[test]
+    fn tool_errors_are_prefixed_and_images_follow_the_tool_results() {
+        let image =
+            json!({ "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } });
+        let request = json!({ "messages": [
+            { "role": "tool", "tool_call_id": "call_1", "is_error": true, "content": "boom" },
+            { "role": "tool", "tool_call_id": "call_2", "content": [{ "type": "text", "text": "chart" }, image] },
+            { "role": "user", "content": "next" }
+        ] });
+
+        let mut body = request.clone();
+        apply(&profile("{}"), &mut body);
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "tool", "tool_call_id": "call_1", "content": "Error: boom" },
+                { "role": "tool", "tool_call_id": "call_2", "content": "chart" },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "Attachments returned by tool call call_2:" },
+                    image
+                ] },
+                { "role": "user", "content": "next" }
+            ])
+        );
+
+        let mut body = request;
+        apply(
+            &profile("{ tool_images: tool_content, tool_error_prefix: 'ERR ' }"),
+            &mut body,
+        );
+        assert_eq!(body["messages"][0]["content"], "ERR boom");
+        assert_eq!(body["messages"][1]["content"][1], image);
+        assert_eq!(body["messages"].as_array().unwrap().len(), 3);
+    }
+}
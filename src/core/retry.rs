@@ -0,0 +1,200 @@
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime};
+
+use crate::core::config::RetryConfig;
+
+/// Upstream headers that announce when a rate limit window resets.
+const RATE_LIMIT_RESET_HEADERS: [&str; 2] =
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"];
+
+/// Exponential backoff policy for retrying a single upstream.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    config: RetryConfig,
+}
+
+impl RetryPolicy {
+    pub fn new(config: RetryConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.config.max_attempts.max(1)
+    }
+
+    pub fn is_retryable(&self, status: StatusCode) -> bool {
+        self.config.retryable_status.contains(&status.as_u16())
+    }
+
+    /// Delay before retry number `attempt` (1-based), given the status and headers of the
+    /// failed response if there was one.
+    ///
+    /// Upstream hints (`Retry-After`, and `x-ratelimit-reset-*` on a 429) take precedence over
+    /// the computed backoff, but are still capped at `max_delay_ms`.
+    pub fn delay(&self, attempt: u32, response: Option<(StatusCode, &HeaderMap)>) -> Duration {
+        let max_delay = Duration::from_millis(self.config.max_delay_ms);
+
+        let hint = response.and_then(|(status, headers)| upstream_delay_hint(status, headers));
+        if let Some(hinted) = hint {
+            return hinted.min(max_delay);
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = Duration::from_millis(self.config.base_delay_ms)
+            .saturating_mul(1 << exponent)
+            .min(max_delay);
+
+        if self.config.jitter {
+            // Equal jitter: keep half the backoff, randomise the other half
+            let half = backoff / 2;
+            half + half.mul_f64(rand::rng().random::<f64>())
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Longest delay requested by the upstream's headers, if any. OpenAI sends the reset headers
+/// on every response, so they only count when the response was a 429.
+fn upstream_delay_hint(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    let retry_after = headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let reset_headers: &[&str] = if status == StatusCode::TOO_MANY_REQUESTS {
+        &RATE_LIMIT_RESET_HEADERS
+    } else {
+        &[]
+    };
+
+    reset_headers
+        .iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(parse_reset_duration)
+        .chain(retry_after)
+        .max()
+}
+
+/// `Retry-After` is either delay-seconds or an HTTP-date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Parses OpenAI-style reset values such as `1s`, `20ms`, `6m0s` or a bare number of seconds.
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let secs = match unit {
+            "ms" => number / 1000.0,
+            "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 3600.0,
+            _ => return None,
+        };
+        total += Duration::try_from_secs_f64(secs).ok()?;
+        rest = tail;
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_durations_in_openai_format() {
+        let ms = Duration::from_millis;
+        assert_eq!(parse_reset_duration("1s"), Some(ms(1000)));
+        assert_eq!(parse_reset_duration("20ms"), Some(ms(20)));
+        assert_eq!(parse_reset_duration("6m0s"), Some(ms(360_000)));
+        assert_eq!(parse_reset_duration("1h2m3.5s"), Some(ms(3_723_500)));
+        assert_eq!(parse_reset_duration(" 2.5 "), Some(ms(2500)));
+    }
+
+    #[test]
+    fn malformed_reset_durations_are_ignored() {
+        assert_eq!(parse_reset_duration(""), None);
+        assert_eq!(parse_reset_duration("soon"), None);
+        assert_eq!(parse_reset_duration("5d"), None);
+        assert_eq!(parse_reset_duration("s"), None);
+        assert_eq!(parse_reset_duration("-1"), None);
+    }
+
+    #[test]
+    fn longest_upstream_hint_wins() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-reset-requests", "1s".parse().unwrap());
+        headers.insert("x-ratelimit-reset-tokens", "6m0s".parse().unwrap());
+        headers.insert(reqwest::header::RETRY_AFTER, "30".parse().unwrap());
+        assert_eq!(
+            upstream_delay_hint(StatusCode::TOO_MANY_REQUESTS, &headers),
+            Some(Duration::from_secs(360))
+        );
+        assert_eq!(
+            upstream_delay_hint(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn reset_headers_only_count_for_rate_limits() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-reset-tokens", "6m0s".parse().unwrap());
+        assert_eq!(
+            upstream_delay_hint(StatusCode::SERVICE_UNAVAILABLE, &headers),
+            None
+        );
+        headers.insert(reqwest::header::RETRY_AFTER, "2".parse().unwrap());
+        assert_eq!(
+            upstream_delay_hint(StatusCode::SERVICE_UNAVAILABLE, &headers),
+            Some(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn hints_are_capped_at_max_delay() {
+        let policy = RetryPolicy::new(RetryConfig {
+            max_attempts: 3,
+            base_delay_ms: 100,
+            max_delay_ms: 5_000,
+            jitter: false,
+            retryable_status: vec![429, 503],
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-reset-tokens", "6m0s".parse().unwrap());
+        assert_eq!(
+            policy.delay(1, Some((StatusCode::TOO_MANY_REQUESTS, &headers))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            policy.delay(2, Some((StatusCode::SERVICE_UNAVAILABLE, &headers))),
+            Duration::from_millis(200)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use crate::common::glob::glob_match;
+use crate::core::config::{Config, FallbackConfig, ModelRouteConfig};
+
+/// Label of requests that only matched the default route.
+const DEFAULT_ROUTE_LABEL: &str = "other";
+
+/// Where a requested model should be sent.
+#[derive(Clone, Debug)]
+pub struct ResolvedRoute {
+    /// Ordered upstream chain: the primary target followed by its fallbacks.
+    pub targets: Vec<RouteTarget>,
+    /// The `routing.models` entry that matched, by its name or glob; `None` for the default
+    /// route. Unlike the requested name, it only takes values from the config.
+    pub entry: Option<String>,
+}
+
+impl ResolvedRoute {
+    pub fn primary(&self) -> &RouteTarget {
+        &self.targets[0]
+    }
+
+    /// The matched entry, or `other` for the default route. Metrics, limits and usage totals
+    /// are kept per label, so junk model names cannot grow them without bound.
+    pub fn label(&self) -> &str {
+        self.entry.as_deref().unwrap_or(DEFAULT_ROUTE_LABEL)
+    }
+}
+
+/// A provider plus the model name to put in the upstream request body.
+#[derive(Clone, Debug)]
+pub struct RouteTarget {
+    pub provider: String,
+    pub model: String,
+}
+
+#[derive(Clone, Debug)]
+struct TargetConfig {
+    provider: String,
+    model: Option<String>,
+}
+
+impl TargetConfig {
+    fn resolve(&self, requested: &str) -> RouteTarget {
+        RouteTarget {
+            provider: self.provider.clone(),
+            model: self.model.clone().unwrap_or_else(|| requested.to_string()),
+        }
+    }
+}
+
+impl From<&FallbackConfig> for TargetConfig {
+    fn from(fallback: &FallbackConfig) -> Self {
+        Self {
+            provider: fallback.provider.clone(),
+            model: fallback.model.clone(),
+        }
+    }
+}
+
+/// A primary target and its ordered fallbacks.
+#[derive(Clone, Debug)]
+struct Chain(Vec<TargetConfig>);
+
+impl Chain {
+    fn resolve(&self, requested: &str, entry: Option<&str>) -> ResolvedRoute {
+        ResolvedRoute {
+            targets: self.0.iter().map(|t| t.resolve(requested)).collect(),
+            entry: entry.map(str::to_string),
+        }
+    }
+}
+
+impl From<&ModelRouteConfig> for Chain {
+    fn from(route: &ModelRouteConfig) -> Self {
+        let primary = TargetConfig {
+            provider: route.provider.clone(),
+            model: route.model.clone(),
+        };
+        Self(
+            std::iter::once(primary)
+                .chain(route.fallbacks.iter().map(TargetConfig::from))
+                .collect(),
+        )
+    }
+}
+
+/// Maps requested model names to upstream providers, optionally aliasing the model.
+pub struct ModelRouter {
+    exact: HashMap<String, Chain>,
+    patterns: Vec<(String, Chain)>,
+    default: Chain,
+}
+
+impl ModelRouter {
+    pub fn new(config: &Config) -> Self {
+        let mut exact = HashMap::new();
+        let mut patterns = Vec::new();
+
+        for route in &config.routing.models {
+            if is_pattern(&route.pattern) {
+                patterns.push((route.pattern.clone(), Chain::from(route)));
+            } else {
+                // First declaration wins, matching the pattern semantics
+                exact
+                    .entry(route.pattern.clone())
+                    .or_insert_with(|| Chain::from(route));
+            }
+        }
+
+        let default_provider = config
+            .routing
+            .default
+            .clone()
+            .unwrap_or_else(|| config.providers[0].name.clone());
+
+        let default_primary = TargetConfig {
+            provider: default_provider,
+            model: None,
+        };
+        let default = Chain(
+            std::iter::once(default_primary)
+                .chain(config.routing.fallbacks.iter().map(TargetConfig::from))
+                .collect(),
+        );
+
+        Self {
+            exact,
+            patterns,
+            default,
+        }
+    }
+
+    pub fn resolve(&self, model: &str) -> ResolvedRoute {
+        if let Some(chain) = self.exact.get(model) {
+            return chain.resolve(model, Some(model));
+        }
+
+        match self
+            .patterns
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, model))
+        {
+            Some((pattern, chain)) => chain.resolve(model, Some(pattern)),
+            None => self.default.resolve(model, None),
+        }
+    }
+
+    /// Model names routed by exact match, with their primary target, sorted by name.
+    pub fn aliases(&self) -> Vec<(String, RouteTarget)> {
+        let mut aliases: Vec<_> = self
+            .exact
+            .iter()
+            .map(|(name, chain)| {
+                (
+                    name.clone(),
+                    chain.resolve(name, Some(name)).primary().clone(),
+                )
+            })
+            .collect();
+        aliases.sort_by(|a, b| a.0.cmp(&b.0));
+        aliases
+    }
+}
+
+fn is_pattern(s: &str) -> bool {
+    s.contains('*') || s.contains('?')
+}
@@ -0,0 +1,95 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Joins the proxy's tag to an upstream signature; never part of a base64 tag.
+const UPSTREAM_SEPARATOR: char = '.';
+
+/// Signs the `thinking` blocks handed to Anthropic clients, so a block sent back in
+/// conversation history can be verified as the proxy's own before it is replayed upstream.
+pub struct ThinkingSigner {
+    key: Vec<u8>,
+}
+
+impl ThinkingSigner {
+    /// Uses the configured secret, or a random one; random keys don't survive a restart,
+    /// so blocks signed before it are then dropped from history instead of replayed.
+    pub fn new(secret: Option<&str>) -> Self {
+        let key = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => rand::rng().random::<[u8; 32]>().to_vec(),
+        };
+        Self { key }
+    }
+
+    /// Base64 HMAC-SHA256 of the thinking text, followed by the upstream's own signature
+    /// when it gave one (Anthropic does), so the block can later be replayed to it.
+    pub fn sign(&self, thinking: &str, upstream: Option<&str>) -> String {
+        let tag = STANDARD.encode(self.mac(thinking).finalize().into_bytes());
+        match upstream {
+            Some(upstream) => format!("{}{}{}", tag, UPSTREAM_SEPARATOR, upstream),
+            None => tag,
+        }
+    }
+
+    pub fn verify(&self, thinking: &str, signature: &str) -> bool {
+        let tag = signature
+            .split_once(UPSTREAM_SEPARATOR)
+            .map_or(signature, |(tag, _)| tag);
+        STANDARD
+            .decode(tag)
+            .is_ok_and(|tag| self.mac(thinking).verify_slice(&tag).is_ok())
+    }
+
+    /// The upstream signature carried by a signature from [`sign`](Self::sign), if any.
+    pub fn upstream_signature(signature: &str) -> Option<&str> {
+        signature
+            .split_once(UPSTREAM_SEPARATOR)
+            .map(|(_, upstream)| upstream)
+    }
+
+    fn mac(&self, thinking: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+        mac.update(thinking.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_verify_only_for_the_signed_text_and_key() {
+        let signer = ThinkingSigner::new(Some("secret"));
+        let signature = signer.sign("let me think", None);
+        assert!(signer.verify("let me think", &signature));
+        assert!(!signer.verify("let me think again", &signature));
+        assert!(!ThinkingSigner::new(Some("other")).verify("let me think", &signature));
+        assert!(!signer.verify("let me think", "not base64!"));
+    }
+
+    #[test]
+    fn random_keys_differ_per_signer() {
+        let signature = ThinkingSigner::new(None).sign("hmm", None);
+        assert!(!ThinkingSigner::new(None).verify("hmm", &signature));
+    }
+
+    #[test]
+    fn upstream_signature_rides_along() {
+        let signer = ThinkingSigner::new(Some("secret"));
+        let plain = signer.sign("hmm", None);
+        assert_eq!(ThinkingSigner::upstream_signature(&plain), None);
+
+        let signature = signer.sign("hmm", Some("EqQBCkYIARgCKkA+/="));
+        assert!(signer.verify("hmm", &signature));
+        assert!(!signer.verify("hmm?", &signature));
+        assert_eq!(
+            ThinkingSigner::upstream_signature(&signature),
+            Some("EqQBCkYIARgCKkA+/=")
+        );
+    }
+}
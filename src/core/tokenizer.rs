@@ -0,0 +1,317 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fancy_regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::core::config::TokenizerConfig;
+
+/// Split pattern of OpenAI's `cl100k_base` encoding.
+const CL100K_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+/// Split pattern of OpenAI's `o200k_base` encoding.
+const O200K_PATTERN: &str = concat!(
+    r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+(?!\S)|\s+"
+);
+
+/// Framing tokens added per chat message, and once to prime the reply.
+const TOKENS_PER_MESSAGE: usize = 3;
+const REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Flat estimate per image; Anthropic bills about 1600 tokens for a ~1.15 megapixel image.
+const IMAGE_TOKENS: usize = 1600;
+
+/// Flat estimate per `file` (PDF) part, about two text-heavy pages; the base64 payload
+/// says little about how much text it encodes.
+const FILE_TOKENS: usize = 3000;
+
+/// Pieces longer than this are estimated rather than merged.
+const MAX_MERGED_PIECE_BYTES: usize = 64;
+
+/// Counts tokens in text. Implementations must be cheap to call concurrently.
+pub trait Tokenizer: Send + Sync {
+    fn name(&self) -> &str;
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Rough estimate of one token per four characters, used when no BPE file is configured.
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+}
+
+/// Byte-pair encoder reading tiktoken `.tiktoken` rank files (`<base64 token> <rank>` per line).
+pub struct BpeTokenizer {
+    name: String,
+    ranks: HashMap<Vec<u8>, u32>,
+    pattern: Regex,
+}
+
+impl BpeTokenizer {
+    pub fn load(path: &Path, pattern: Option<&str>) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut ranks = HashMap::new();
+        for (index, line) in content.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let (Some(token), Some(rank)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let token = STANDARD
+                .decode(token)
+                .map_err(|e| format!("line {}: {}", index + 1, e))?;
+            let rank = rank
+                .parse()
+                .map_err(|e| format!("line {}: {}", index + 1, e))?;
+            ranks.insert(token, rank);
+        }
+        if ranks.is_empty() {
+            return Err("no ranks found".to_string());
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("bpe")
+            .to_string();
+        // Named presets, a custom regex, or the preset matching the file name
+        let pattern = match pattern.unwrap_or(&name) {
+            "o200k_base" => O200K_PATTERN,
+            "cl100k_base" => CL100K_PATTERN,
+            custom if pattern.is_some() => custom,
+            _ => CL100K_PATTERN,
+        };
+        let pattern = Regex::new(pattern).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            name,
+            ranks,
+            pattern,
+        })
+    }
+
+    /// Number of tokens one pre-split piece merges into, per tiktoken's byte-pair merge.
+    ///
+    /// Merging costs O(n²) in the piece length, and the split patterns let runs of
+    /// whitespace or symbols through as one piece, so long pieces are estimated instead.
+    fn count_piece(&self, piece: &str) -> usize {
+        let bytes = piece.as_bytes();
+        if bytes.len() > MAX_MERGED_PIECE_BYTES {
+            return HeuristicTokenizer.count(piece);
+        }
+        if self.ranks.contains_key(bytes) {
+            return 1;
+        }
+
+        // Start of each current part, with the rank of merging it with the next part; merge
+        // the lowest-ranked pair until none is known, updating only the neighbouring ranks
+        let rank = |parts: &[(usize, u32)], i: usize| {
+            parts
+                .get(i + 2)
+                .and_then(|end| self.ranks.get(&bytes[parts[i].0..end.0]))
+                .copied()
+                .unwrap_or(u32::MAX)
+        };
+        let mut parts: Vec<(usize, u32)> = (0..=bytes.len()).map(|i| (i, u32::MAX)).collect();
+        for i in 0..parts.len() {
+            parts[i].1 = rank(&parts, i);
+        }
+        while let Some((i, _)) = parts
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, rank))| *rank != u32::MAX)
+            .min_by_key(|(_, (_, rank))| *rank)
+        {
+            parts.remove(i + 1);
+            parts[i].1 = rank(&parts, i);
+            if i > 0 {
+                parts[i - 1].1 = rank(&parts, i - 1);
+            }
+        }
+        parts.len() - 1
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count(&self, text: &str) -> usize {
+        self.pattern
+            .find_iter(text)
+            .filter_map(Result::ok)
+            .map(|piece| self.count_piece(piece.as_str()))
+            .sum()
+    }
+}
+
+/// Builds the configured tokenizer, falling back to the heuristic when no BPE file loads.
+pub fn from_config(config: &TokenizerConfig) -> Box<dyn Tokenizer> {
+    let Some(path) = &config.bpe_file else {
+        info!("No tokenizer bpe_file configured, estimating tokens from character counts");
+        return Box::new(HeuristicTokenizer);
+    };
+    match BpeTokenizer::load(path, config.pattern.as_deref()) {
+        Ok(tokenizer) => {
+            info!(
+                tokenizer = tokenizer.name(),
+                ranks = tokenizer.ranks.len(),
+                "Loaded BPE tokenizer"
+            );
+            Box::new(tokenizer)
+        }
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to load BPE tokenizer, estimating tokens from character counts");
+            Box::new(HeuristicTokenizer)
+        }
+    }
+}
+
+/// Estimates the prompt tokens of an OpenAI chat completions request.
+pub fn count_chat_request(tokenizer: &dyn Tokenizer, body: &Value) -> usize {
+    let mut total = REPLY_PRIMING_TOKENS;
+
+    for message in body
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+    {
+        total += TOKENS_PER_MESSAGE;
+        if let Some(role) = message.get("role").and_then(|r| r.as_str()) {
+            total += tokenizer.count(role);
+        }
+        match message.get("content") {
+            Some(Value::String(text)) => total += tokenizer.count(text),
+            Some(Value::Array(parts)) => {
+                for part in parts {
+                    match part.get("type").and_then(|t| t.as_str()) {
+                        Some("text") => {
+                            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                total += tokenizer.count(text);
+                            }
+                        }
+                        Some("image_url") => total += IMAGE_TOKENS,
+                        Some("file") => total += FILE_TOKENS,
+                        _ => total += tokenizer.count(&part.to_string()),
+                    }
+                }
+            }
+            _ => {}
+        }
+        for call in message
+            .get("tool_calls")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(function) = call.get("function") {
+                for field in ["name", "arguments"] {
+                    if let Some(text) = function.get(field).and_then(|v| v.as_str()) {
+                        total += tokenizer.count(text);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(tools) = body.get("tools").filter(|t| !t.is_null()) {
+        total += tokenizer.count(&tools.to_string());
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Ranks for the bytes `a`, `b`, `c`, space, and the merges `ab` and `abc`.
+    fn tokenizer() -> BpeTokenizer {
+        let ranks = ["a", "b", "c", " ", "ab", "abc"]
+            .iter()
+            .enumerate()
+            .map(|(rank, token)| format!("{} {}\n", STANDARD.encode(token), rank))
+            .collect::<String>();
+        let path = std::env::temp_dir().join(format!("ranks-{}.tiktoken", std::process::id()));
+        fs::write(&path, ranks).unwrap();
+        let tokenizer = BpeTokenizer::load(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+        tokenizer
+    }
+
+    #[test]
+    fn bpe_merges_known_pairs_per_piece() {
+        let tokenizer = tokenizer();
+        assert_eq!(tokenizer.count(""), 0);
+        // "abc" is a single rank
+        assert_eq!(tokenizer.count("abc"), 1);
+        // " ab" merges to " " + "ab", "cab" to "c" + "ab"
+        assert_eq!(tokenizer.count(" ab"), 2);
+        assert_eq!(tokenizer.count("cab"), 2);
+        assert_eq!(tokenizer.count("abc ab"), 3);
+    }
+
+    #[test]
+    fn long_pieces_are_estimated() {
+        let tokenizer = tokenizer();
+        // One piece of 64 bytes still merges to 32 "ab" tokens, a longer one is estimated
+        assert_eq!(tokenizer.count(&"ab".repeat(32)), 32);
+        assert_eq!(tokenizer.count(&"ab".repeat(50)), 25);
+    }
+
+    #[test]
+    fn load_rejects_bad_rank_files() {
+        let path = std::env::temp_dir().join(format!("bad-{}.tiktoken", std::process::id()));
+        fs::write(&path, "not-base64! 0\n").unwrap();
+        assert!(BpeTokenizer::load(&path, None)
+            .err()
+            .unwrap()
+            .starts_with("line 1"));
+        fs::write(&path, "\n").unwrap();
+        assert_eq!(
+            BpeTokenizer::load(&path, None).err().unwrap(),
+            "no ranks found"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn chat_requests_count_framing_parts_and_tool_calls() {
+        let body = json!({
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "text", "text": "abcdefgh"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+                    {"type": "file", "file": {"file_data": "data:application/pdf;base64,AAAA"}}
+                ]},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "get", "arguments": "{}"}}
+                ]}
+            ]
+        });
+        let expected = REPLY_PRIMING_TOKENS
+            + TOKENS_PER_MESSAGE
+            + 1
+            + 2
+            + IMAGE_TOKENS
+            + FILE_TOKENS
+            + TOKENS_PER_MESSAGE
+            + 3
+            + 1
+            + 1;
+        assert_eq!(count_chat_request(&HeuristicTokenizer, &body), expected);
+    }
+}
@@ -0,0 +1,48 @@
+use serde_json::Value;
+
+/// Who made a request and where it was served, for attributing its usage.
+#[derive(Clone, Debug)]
+pub struct UsageScope {
+    pub client: String,
+    /// Model name as requested by the client.
+    pub model: String,
+    /// [`ResolvedRoute::label`](crate::core::router::ResolvedRoute::label) of the request.
+    pub route: String,
+    pub provider: String,
+    /// Model name that was sent to the provider.
+    pub upstream_model: String,
+}
+
+/// Token counts reported by the upstream for one request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Portion of `input_tokens` served from the upstream's prompt cache.
+    pub cached_tokens: u64,
+}
+
+impl Usage {
+    /// Reads an OpenAI `usage` object.
+    pub fn from_openai(usage: &Value) -> Self {
+        Self {
+            input_tokens: usage
+                .get("prompt_tokens")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(0),
+            output_tokens: usage
+                .get("completion_tokens")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(0),
+            cached_tokens: usage
+                .get("prompt_tokens_details")
+                .and_then(|d| d.get("cached_tokens"))
+                .and_then(|t| t.as_u64())
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
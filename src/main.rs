@@ -3,16 +3,33 @@ mod common;
 mod core;
 
 use axum::{
-    routing::{get, post},
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
     Json, Router,
 };
 use std::env;
+use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
+use crate::api::auth::{self, ClientKeys};
+use crate::api::metrics;
 use crate::api::routes;
-use crate::core::{Config, OpenAiClient};
+use crate::api::state::AppState;
+use crate::core::accounting::UsageLedger;
+use crate::core::batches::BatchStore;
+use crate::core::files::FileStore;
+use crate::core::health::HealthMonitor;
+use crate::core::limits::RateLimiter;
+use crate::core::message_batches::MessageBatchStore;
+use crate::core::metrics::Metrics;
+use crate::core::pricing::PriceTable;
+use crate::core::signing::ThinkingSigner;
+use crate::core::{tokenizer, Config, ModelRouter, OpenAiClient};
 
 #[cfg(windows)]
 fn hide_console() {
@@ -51,39 +68,107 @@ async fn main() {
         .init();
 
     let config = Config::load();
-    let client = OpenAiClient::new(config.clone());
+    // Built first so a passthrough path that clashes with the proxy's own stops startup
+    let api_routes = routes::api_routes(&config.passthrough);
+    let client = OpenAiClient::new(&config);
 
     info!(
         port = config.port,
-        base_url = %config.openai_base_url,
+        providers = config.providers.len(),
         config_path = %Config::get_config_path().display(),
         "Starting Proxy API"
     );
 
-    // Initial connection check
-    if let Err(e) = client.check_connection().await {
-        tracing::warn!("⚠️  COULD NOT CONNECT TO API: {}", e);
-        eprintln!("\n**************************************************");
-        eprintln!("WARNING: Could not connect to the OpenAI API!");
-        eprintln!("Error: {}", e);
-        eprintln!("Please check your internet connection or base_url.");
-        eprintln!("**************************************************\n");
+    // Initial connection check, then keep probing in the background for readiness
+    let monitor = Arc::new(HealthMonitor::new(client.clone(), &config.health));
+    for upstream in monitor.probe_all().await {
+        if let Some(e) = upstream
+            .error
+            .as_ref()
+            .filter(|_| !upstream.state.is_usable())
+        {
+            tracing::warn!(
+                provider = %upstream.name,
+                "⚠️  COULD NOT CONNECT TO API: {}",
+                e
+            );
+            eprintln!("\n**************************************************");
+            eprintln!(
+                "WARNING: Could not connect to upstream '{}'!",
+                upstream.name
+            );
+            eprintln!("Error: {}", e);
+            eprintln!("Please check your internet connection, base_url and api_key.");
+            eprintln!("**************************************************\n");
+        } else {
+            info!(
+                provider = %upstream.name,
+                base_url = client.provider(&upstream.name).map(|p| p.base_url()),
+                "Successfully connected to Upstream API"
+            );
+        }
+    }
+    monitor.clone().spawn();
+
+    let state = AppState {
+        client,
+        router: Arc::new(ModelRouter::new(&config)),
+        client_keys: Arc::new(ClientKeys::new(&config.auth)),
+        limiter: Arc::new(RateLimiter::new(&config.auth)),
+        ledger: Arc::new(UsageLedger::open(&config.usage_log)),
+        prices: Arc::new(PriceTable::new(&config.pricing)),
+        metrics: Arc::new(Metrics::new()),
+        health: monitor,
+        tokenizer: Arc::from(tokenizer::from_config(&config.tokenizer)),
+        message_batches: Arc::new(MessageBatchStore::open(&config.batches)),
+        batches: Arc::new(BatchStore::open(&config.batches)),
+        files: Arc::new(FileStore::open(&config.batches.files_dir())),
+        signer: Arc::new(ThinkingSigner::new(config.thinking_signing_key.as_deref())),
+    };
+    routes::message_batches::resume(&state);
+    routes::batches::resume(&state);
+
+    if state.client_keys.is_enabled() {
+        info!(
+            keys = config.auth.keys.len(),
+            "Client authentication enabled"
+        );
     } else {
-        info!("Successfully connected to Upstream API");
+        tracing::warn!("No auth keys configured, the proxy is open to anyone who can reach it");
     }
 
+    // Client-facing API, guarded by virtual key authentication
+    let api = api_routes
+        .into_iter()
+        .fold(Router::new(), |api, (path, route)| api.route(&path, route))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track,
+        ));
+
+    // Operator endpoints, guarded by the admin key
+    let admin = Router::new()
+        .route("/admin/usage", get(routes::admin::usage))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate_admin,
+        ));
+
     let app = Router::new()
         // Health check
         .route("/health", get(health))
-        // OpenAI-compatible endpoint
-        .route(
-            "/v1/chat/completions",
-            post(routes::openai::chat_completions),
-        )
-        // Anthropic-compatible endpoint
-        .route("/v1/messages", post(routes::anthropic::messages))
+        // Readiness, based on the background upstream probes
+        .route("/health/ready", get(ready))
+        // Prometheus scrape endpoint
+        .route("/metrics", get(metrics::render))
+        .merge(api)
+        .merge(admin)
         .layer(CorsLayer::permissive())
-        .with_state(client);
+        .with_state(state);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port))
         .await
@@ -100,3 +185,20 @@ async fn health() -> Json<serde_json::Value> {
         "service": "proxy-api"
     }))
 }
+
+async fn ready(State(state): State<AppState>) -> Response {
+    let ready = state.health.is_ready();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(serde_json::json!({
+            "status": if ready { "ready" } else { "unavailable" },
+            "upstreams": state.health.snapshot(),
+        })),
+    )
+        .into_response()
+}